@@ -0,0 +1,16 @@
+// With the `header` feature, generates the C header of the `extern "C"` functions of src/ffi.rs in
+// OUT_DIR, where a test compares it to include/expert_system.h
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+        let out = std::env::var("OUT_DIR").unwrap_or_default();
+        match cbindgen::generate(&dir) {
+            Ok(bindings) => drop(bindings.write_to_file(format!("{out}/expert_system.h"))),
+            Err(e) => println!("cargo:warning=cannot generate expert_system.h: {e}")
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
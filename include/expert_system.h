@@ -0,0 +1,75 @@
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#ifndef EXPERT_SYSTEM_H
+#define EXPERT_SYSTEM_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Answer of a query that could not be made, see `es_last_error`
+#define ES_ERROR -1
+
+#define ES_FALSE 0
+
+#define ES_TRUE 1
+
+#define ES_UNDETERMINED 2
+
+// A knowledge base, only handled through pointers
+typedef struct EsKnowledgeBase EsKnowledgeBase;
+
+// Creates an empty knowledge base, to free with `es_free`. Texts of the engine are never colored
+struct EsKnowledgeBase *es_new(void);
+
+// Frees a knowledge base, null is ignored
+//
+// # Safety
+// `kb` comes from `es_new` and is not used afterwards
+void es_free(struct EsKnowledgeBase *kb);
+
+// Adds the rules, facts and queries of a rule file's text. Returns 0, or the exit status of
+// the command line for the error: 3 for a contradiction, 4 for a parse error, 5 for an unexpected
+// error and 64 for a bad argument. The knowledge base is left as it was on error
+//
+// # Safety
+// `kb` comes from `es_new` and `rules` is a nul terminated string
+int es_load(struct EsKnowledgeBase *kb, const char *rules);
+
+// Sets a variable or an alias as a fact, a false alias sets its variable to false, or clears the fact. Returns 0, or 64 for an unknown name
+//
+// # Safety
+// `kb` comes from `es_new` and `name` is a nul terminated string
+int es_set_fact(struct EsKnowledgeBase *kb,
+                const char *name,
+                bool value);
+
+// Answers a variable or an alias: `ES_TRUE`, `ES_FALSE`, `ES_UNDETERMINED` or `ES_ERROR`
+//
+// # Safety
+// `kb` comes from `es_new` and `name` is a nul terminated string
+int es_query(struct EsKnowledgeBase *kb, const char *name);
+
+// The trace that leads to the answer of a variable or an alias, as UTF-8 to free with
+// `es_string_free`. Null on error, see `es_last_error`
+//
+// # Safety
+// `kb` comes from `es_new` and `name` is a nul terminated string
+char *es_explain(struct EsKnowledgeBase *kb, const char *name);
+
+// Frees a string returned by `es_explain`, null is ignored
+//
+// # Safety
+// `string` comes from `es_explain` and is not used afterwards
+void es_string_free(char *string);
+
+// The message of the error of the last call as UTF-8, empty when it succeeded. Owned by the
+// knowledge base until its next call
+//
+// # Safety
+// `kb` comes from `es_new`
+const char *es_last_error(const struct EsKnowledgeBase *kb);
+
+#endif  /* EXPERT_SYSTEM_H */
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
-use crate::models::{Variable, Operator};
+use crate::models::{Variable, Operator, Answer};
 use crate::utils::print_history;
-use crate::translate::Lang;
+use crate::translate::{Lang, Error};
 
-pub fn algo_v1(variables: &mut HashMap<char, Variable>, trace: bool, lang: &Lang) {
+pub fn algo_v1(variables: &mut HashMap<char, Variable>, trace: bool, lang: &Lang) -> Vec<(char, Answer)> {
     let requested: Vec<char> = variables.iter().filter(|(_,v)| v.requested == true).map(|(k,_)| *k).collect();
+    let mut answers: Vec<(char, Answer)> = Vec::new();
     for c in requested {
         let history = String::new();
         match search_query(c, variables, &mut Vec::new(), history) {
@@ -14,16 +15,34 @@ pub fn algo_v1(variables: &mut HashMap<char, Variable>, trace: bool, lang: &Lang
                 } else {
                     println!("{} is {}", c, res);
                 }
+                answers.push((c, match res {
+                    true => Answer::True,
+                    false => Answer::False
+                }));
             },
-            Err(e) => println!("{} => {}", c, e)
+            Err(e) => {
+                println!("{} => {}", c, e.message(lang));
+                answers.push((c, Answer::Undetermined));
+            }
         }
     }
+    answers
+}
+
+pub(crate) fn evaluate(variables: &mut HashMap<char, Variable>, queries: &[char]) -> Vec<(char, Answer)> {
+    queries.iter().map(|c| {
+        match search_query(*c, variables, &mut Vec::new(), String::new()) {
+            Ok((true, _)) => (*c, Answer::True),
+            Ok((false, _)) => (*c, Answer::False),
+            Err(_) => (*c, Answer::Undetermined)
+        }
+    }).collect()
 }
 
-pub fn search_query(query: char, variables: &mut HashMap<char, Variable>, old_rules: &mut Vec<String>, mut history: String) -> Result<(bool, String), String> {
+pub(crate) fn search_query(query: char, variables: &mut HashMap<char, Variable>, old_rules: &mut Vec<String>, mut history: String) -> Result<(bool, String), Error> {
     for rule in variables.get(&query).unwrap().rules.iter() {
         if old_rules.contains(&rule.formula_string.clone()) == true {
-            return Err("Error: the rule loop".to_string());
+            return Err(Error::RuleLoop);
         }
     }
 
@@ -52,7 +71,7 @@ pub fn search_query(query: char, variables: &mut HashMap<char, Variable>, old_ru
                 if variables.get(&c).unwrap().locked == false {
                     let mut new_vec = old_rules.clone();
                     if new_vec.contains(&query_rules[i].formula_string.clone()) == true {
-                        return Err("Error: the rule loop".to_string());
+                        return Err(Error::RuleLoop);
                     } else {
                         new_vec.push(query_rules[i].formula_string.clone());
                     }
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, write};
+use crate::models::{Variable, Answer, ExitStatus};
+use crate::parsing::fill_maps;
+use crate::algo::evaluate;
+use crate::translate::Error;
+
+/* ---------- CSV ---------- */
+fn csv_to_rows(contents: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut scoped = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, scoped) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', _) => scoped = !scoped,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            ('\r', false) => (),
+            _ => field.push(c)
+        }
+    }
+    if scoped {
+        Err(Error::UnclosedQuoteCsv)?
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows.into_iter().filter(|row| row.iter().any(|field| !field.trim().is_empty())).collect())
+}
+
+fn to_csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => String::from(field)
+    }
+}
+
+fn to_csv_line(fields: &[String]) -> String {
+    fields.iter().map(|field| to_csv_field(field)).collect::<Vec<String>>().join(",")
+}
+
+/* ---------- BATCH ---------- */
+fn resolve_column(variables: &HashMap<char, Variable>, name: &str) -> Result<(char, bool), Error> {
+    match variables.iter().find(|(k, v)| k.to_string() == name || v.alias_true.as_deref() == Some(name) || v.alias_false.as_deref() == Some(name)) {
+        Some((k, v)) => Ok((*k, v.alias_false.as_deref() != Some(name))),
+        None => Err(Error::DoesNotExist(String::from(name)))
+    }
+}
+
+// Cells override the facts of the files, a blank one keeps them
+fn set_case(variables: &mut HashMap<char, Variable>, columns: &[(char, bool)], row: &[String], line: usize) -> Result<(), Error> {
+    for (i, cell) in row.iter().enumerate() {
+        let (key, positive) = match columns.get(i) {
+            Some(column) => *column,
+            None => Err(Error::UnexpectedCell(line.to_string(), cell.clone()))?
+        };
+        let answer = match cell.trim() {
+            "" => Answer::Undetermined,
+            value => match Answer::from_str(value) {
+                Some(answer) => answer,
+                None => Err(Error::InvalidFact(line.to_string(), value.to_string()))?
+            }
+        };
+        let answer = match positive {
+            true => answer,
+            false => answer.negate()
+        };
+        if let Some(variable) = variables.get_mut(&key) {
+            match answer {
+                Answer::True | Answer::False => {
+                    variable.value = answer == Answer::True;
+                    variable.locked = true;
+                },
+                Answer::Undetermined => ()
+            }
+        }
+    }
+    Ok(())
+}
+
+// Rows of the results, header first, and the status of the whole batch
+fn batch_rows(files: &[String], facts: &str) -> Result<(Vec<Vec<String>>, usize, ExitStatus), Error> {
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    for file in files {
+        fill_maps(&mut variables, file, true).map_err(|e| Error::in_file(file, e))?;
+    }
+    let rows = csv_to_rows(&read_to_string(facts).map_err(|e| Error::Io(String::from(facts), e.to_string()))?).map_err(|e| Error::in_file(facts, e))?;
+    let (header, cases) = match rows.split_first() {
+        Some(split) => split,
+        None => Err(Error::EmptyFile(String::from(facts)))?
+    };
+    let columns = header.iter().map(|name| resolve_column(&variables, name.trim())).collect::<Result<Vec<(char, bool)>, Error>>().map_err(|e| Error::in_file(facts, e))?;
+    let mut queries: Vec<char> = variables.iter().filter(|(_, v)| v.requested).map(|(k, _)| *k).collect();
+    queries.sort();
+    let mut lines = vec![header.iter().cloned().chain(queries.iter().map(|k| {
+        variables.get(k).and_then(|v| v.alias_true.clone()).unwrap_or(k.to_string())
+    })).collect::<Vec<String>>()];
+    let mut status = ExitStatus::AllTrue;
+    for (i, case) in cases.iter().enumerate() {
+        let mut case_variables = variables.clone();
+        set_case(&mut case_variables, &columns, case, i + 2).map_err(|e| Error::in_file(facts, e))?;
+        let answers = evaluate(&mut case_variables, &queries);
+        status = status.max(ExitStatus::from_answers(&answers));
+        let mut cells = case.clone();
+        cells.resize(header.len().max(case.len()), String::new());
+        cells.extend(answers.iter().map(|(_, answer)| answer.to_string()));
+        lines.push(cells);
+    }
+    Ok((lines, queries.len(), status))
+}
+
+fn write_rows(rows: &[Vec<String>], output: Option<&String>) -> Result<(), Error> {
+    let contents = rows.iter().map(|row| to_csv_line(row)).collect::<Vec<String>>().join("\n") + "\n";
+    match output {
+        Some(path) => write(path, contents).map_err(|e| Error::Io(path.clone(), e.to_string()))?,
+        None => print!("{contents}")
+    }
+    Ok(())
+}
+
+pub fn batch_mode(files: &[String], facts: &str, output: Option<&String>) -> Result<ExitStatus, Error> {
+    let (rows, _, status) = batch_rows(files, facts)?;
+    write_rows(&rows, output)?;
+    Ok(status)
+}
+
+// Answers of each case named by their csv line, the output file is rewritten on each run
+pub fn batch_results(files: &[String], facts: &str, output: Option<&String>) -> Result<Vec<(String, String)>, Error> {
+    let (rows, queries, _) = batch_rows(files, facts)?;
+    if output.is_some() {
+        write_rows(&rows, output)?;
+    }
+    let (header, cases) = rows.split_first().unwrap();
+    let names = &header[header.len() - queries..];
+    Ok(cases.iter().enumerate().flat_map(|(i, case)| {
+        names.iter().zip(case[case.len() - queries..].iter()).map(move |(name, answer)| (format!("line {} {name}", i + 2), answer.clone()))
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::fill_source;
+
+    fn strings(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| String::from(*field)).collect()
+    }
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        let rows = csv_to_rows("A,\"It, \"\"rains\"\"\"\r\ntrue,\n\n , \nfalse,\"a\nb\"").unwrap();
+        assert_eq!(rows, vec![strings(&["A", "It, \"rains\""]), strings(&["true", ""]), strings(&["false", "a\nb"])]);
+    }
+
+    #[test]
+    fn an_unclosed_quote_is_an_error() {
+        assert_eq!(csv_to_rows("A,\"B\ntrue"), Err(Error::UnclosedQuoteCsv));
+    }
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        assert_eq!(to_csv_line(&strings(&["A", "x,y", "say \"hi\""])), "A,\"x,y\",\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn false_aliases_negate_their_column() {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        fill_source(&mut variables, "def A \"sunny\" \"rainy\"\nB => C", true).unwrap();
+        let columns = vec![resolve_column(&variables, "rainy").unwrap(), resolve_column(&variables, "B").unwrap()];
+        set_case(&mut variables, &columns, &strings(&["true", ""]), 2).unwrap();
+        assert!(!variables[&'A'].value && variables[&'A'].locked);
+        assert!(!variables[&'B'].locked);
+        assert_eq!(set_case(&mut variables, &columns, &strings(&["maybe"]), 3), Err(Error::InvalidFact(String::from("3"), String::from("maybe"))));
+        assert_eq!(set_case(&mut variables, &columns, &strings(&["1", "0", "1"]), 4), Err(Error::UnexpectedCell(String::from("4"), String::from("1"))));
+    }
+
+    #[test]
+    fn a_contradiction_keeps_its_status_once_named() {
+        let file = format!("{}/test_files/bad/bad_loop.ru", env!("CARGO_MANIFEST_DIR"));
+        let facts = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        match batch_rows(&[file.clone()], &facts) {
+            Err(e) => {
+                assert!(matches!(&e, Error::InFile(named, _) if *named == file));
+                assert_eq!(ExitStatus::from_error(&e), ExitStatus::Inconsistent);
+            },
+            Ok(_) => panic!("{file} loaded")
+        }
+    }
+
+    #[test]
+    fn blank_cells_keep_the_facts_of_the_file() {
+        let dir = std::env::temp_dir();
+        let (rules, facts) = (dir.join(format!("expert-system-batch-{}.ru", std::process::id())), dir.join(format!("expert-system-batch-{}.csv", std::process::id())));
+        std::fs::write(&rules, "=A\nA | B => C\n?C\n").unwrap();
+        std::fs::write(&facts, "A,B\n,false\nfalse,\nfalse,true\n").unwrap();
+        let (rows, _, _) = batch_rows(&[rules.display().to_string()], &facts.display().to_string()).unwrap();
+        let answers: Vec<&str> = rows[1..].iter().map(|row| row[2].as_str()).collect();
+        assert_eq!(answers, vec!["true", "false", "true"]);
+        std::fs::remove_file(&rules).unwrap();
+        std::fs::remove_file(&facts).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::read_to_string;
+use std::env::var;
+use std::path::PathBuf;
+use clap::ValueEnum;
+use colored::control::{set_override, unset_override};
+use crate::translate::{Lang, Error};
+use crate::utils::{parse_key_values, config_dir};
+use crate::leakser::Flag;
+
+// Engines a config file can choose, `algo_v1` is the only one for now
+const ENGINES: [&str; 1] = ["v1"];
+
+/* ---------- COLOR ---------- */
+#[derive(PartialEq, Clone, Copy, ValueEnum)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never
+}
+
+impl ColorMode {
+    pub(crate) fn from_str(string: &str) -> Option<ColorMode> {
+        match string.to_lowercase().trim() {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None
+        }
+    }
+
+    // Auto colors a terminal unless `NO_COLOR` is set, `CLICOLOR_FORCE` forces it
+    pub fn apply(&self) {
+        match self {
+            ColorMode::Auto => unset_override(),
+            ColorMode::Always => set_override(true),
+            ColorMode::Never => set_override(false)
+        }
+    }
+}
+
+/* ---------- CONFIG ---------- */
+pub struct Config {
+    lang: Option<Lang>,
+    trace: bool,
+    color: Option<ColorMode>,
+    plain: bool,
+    prompt: Option<String>
+}
+
+fn config_file() -> Option<PathBuf> {
+    match var("EXPERT_SYSTEM_CONFIG") {
+        Ok(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => config_dir().map(|dir| dir.join("config"))
+    }
+}
+
+fn parse_switch(key: &str, value: &str) -> Result<bool, Error> {
+    match value.to_lowercase().trim() {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(Error::ConfigValue(String::from(key), String::from(value)))
+    }
+}
+
+impl Config {
+    // Settings of the user config file, nothing is set when it does not exist
+    pub fn load() -> Result<Config, Error> {
+        let path = match config_file() {
+            Some(path) if path.exists() => path,
+            _ => return Config::parse("")
+        };
+        let contents = read_to_string(&path).map_err(|e| Error::Io(path.display().to_string(), e.to_string()))?;
+        Config::parse(&contents).map_err(|e| Error::in_file(&path.display().to_string(), e))
+    }
+
+    fn parse(contents: &str) -> Result<Config, Error> {
+        let mut config = Config { lang: None, trace: false, color: None, plain: false, prompt: None };
+        for (key, value) in parse_key_values(contents) {
+            match key.as_str() {
+                "lang" | "language" => Lang::from_code(&value).map(|lang| config.lang = Some(lang)),
+                "trace" => parse_switch(&key, &value).map(|trace| config.trace = trace),
+                "plain" => parse_switch(&key, &value).map(|plain| config.plain = plain),
+                "color" => ColorMode::from_str(&value).map(|color| config.color = Some(color)).ok_or(Error::ConfigValue(key.clone(), value.clone())),
+                "engine" if ENGINES.contains(&value.trim()) => Ok(()),
+                "engine" => Err(Error::UnknownEngine(value.clone(), ENGINES.join(", "))),
+                "prompt" => {
+                    config.prompt = Some(value.clone());
+                    Ok(())
+                },
+                _ => Err(Error::ConfigKey(key.clone()))
+            }?;
+        }
+        Ok(config)
+    }
+
+    // Completes the flags of the command line, which always win, the environment comes before the file.
+    // `--no-trace` and `--no-plain` turn off what the file turns on
+    pub fn apply(self, flags: &mut Vec<Flag>) {
+        if !flags.iter().any(|flag| matches!(flag, Flag::Lang(_))) {
+            flags.push(Flag::Lang(Lang::from_env().or(self.lang).unwrap_or_default()));
+        }
+        if self.trace && !flags.contains(&Flag::Trace) && !flags.contains(&Flag::NoTrace) {
+            flags.push(Flag::Trace);
+        }
+        if self.plain && !flags.contains(&Flag::Plain) && !flags.contains(&Flag::NoPlain) {
+            flags.push(Flag::Plain);
+        }
+        // a `NO_COLOR` environment wins over the file
+        if let Some(color) = self.color.filter(|_| var("NO_COLOR").map(|v| v.is_empty()).unwrap_or(true)) {
+            if !flags.iter().any(|flag| matches!(flag, Flag::Color(_))) {
+                flags.push(Flag::Color(color));
+            }
+        }
+        if let Some(prompt) = self.prompt {
+            flags.push(Flag::Prompt(prompt));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config { lang: Some(Lang::default()), trace: true, color: None, plain: true, prompt: None }
+    }
+
+    #[test]
+    fn command_line_turns_off_the_file() {
+        let mut flags = vec![Flag::NoTrace, Flag::NoPlain];
+        config().apply(&mut flags);
+        assert!(!flags.contains(&Flag::Trace) && !flags.contains(&Flag::Plain));
+        let mut flags = Vec::new();
+        config().apply(&mut flags);
+        assert!(flags.contains(&Flag::Trace) && flags.contains(&Flag::Plain));
+    }
+
+    #[test]
+    fn switches_take_words() {
+        assert!(parse_switch("trace", "On").unwrap() && !parse_switch("trace", "no").unwrap());
+        assert!(matches!(parse_switch("trace", "v1"), Err(Error::ConfigValue(..))));
+    }
+
+    #[test]
+    fn engine_is_v1_only() {
+        assert!(Config::parse("engine = v1\n").is_ok());
+        assert!(matches!(Config::parse("engine = v2\n"), Err(Error::UnknownEngine(..))));
+        assert!(matches!(Config::parse("motor = v1\n"), Err(Error::ConfigKey(_))));
+    }
+}
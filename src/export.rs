@@ -0,0 +1,520 @@
+use std::collections::{HashMap, HashSet, BTreeMap};
+use clap::ValueEnum;
+use crate::models::{Variable, Rule, Operator, BTree};
+use crate::translate::Error;
+
+#[derive(PartialEq, Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// Rule dependency graph for graphviz
+    Dot,
+    /// Whole knowledge base, read back by `import json`
+    Json,
+    /// Rules and facts as CNF for SAT solvers
+    Dimacs,
+    /// Rules and facts with a satisfiability check per query for SMT solvers
+    Smt2,
+    /// Rules as clauses, facts and queries as goals for Prolog
+    Prolog
+}
+
+pub fn export(variables: &HashMap<char, Variable>, format: Format) -> Result<String, Error> {
+    match format {
+        Format::Dot => Ok(to_dot(variables)),
+        Format::Json => Ok(to_json(variables)),
+        Format::Dimacs => to_dimacs(variables),
+        Format::Smt2 => Ok(to_smt2(variables)),
+        Format::Prolog => Ok(to_prolog(variables))
+    }
+}
+
+// Formats of the interactive `export`, where `graph` names `dot` as it did before the other formats
+pub(crate) fn format_named(name: &str) -> Option<Format> {
+    match name {
+        "graph" => Some(Format::Dot),
+        name => Format::from_str(name, true).ok()
+    }
+}
+
+pub(crate) fn format_names() -> String {
+    Format::value_variants().iter()
+        .filter_map(|format| format.to_possible_value())
+        .map(|value| match value.get_name() {
+            "dot" => String::from("graph"),
+            name => name.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/* ---------- HELPERS ---------- */
+pub(crate) fn unique_rules(variables: &HashMap<char, Variable>) -> Vec<Rule> {
+    let mut keys: Vec<&char> = variables.keys().collect();
+    keys.sort();
+    let mut rules: Vec<Rule> = Vec::new();
+    for key in keys {
+        for rule in variables[key].rules.iter() {
+            if !rules.iter().any(|r| r.formula_string == rule.formula_string) {
+                rules.push(rule.clone());
+            }
+        }
+    }
+    rules
+}
+
+fn rule_variables(rule: &Rule) -> (Vec<char>, Vec<char>) {
+    let to_chars = |nodes: Vec<Operator>| {
+        let mut chars: Vec<char> = nodes.iter().filter_map(|node| match node {Operator::Var(v) => Some(*v), _ => None}).collect();
+        chars.sort();
+        chars.dedup();
+        chars
+    };
+    (
+        to_chars(rule.input.find_nodes(|ope| matches!(ope, Operator::Var(_)))),
+        to_chars(rule.output.find_nodes(|ope| matches!(ope, Operator::Var(_))))
+    )
+}
+
+fn sorted_keys(variables: &HashMap<char, Variable>) -> Vec<char> {
+    let mut keys: Vec<char> = variables.keys().copied().collect();
+    keys.sort();
+    keys
+}
+
+fn aliases(variable: &Variable) -> String {
+    [&variable.alias_true, &variable.alias_false].into_iter().flatten().map(|alias| format!(" \"{alias}\"")).collect()
+}
+
+// The operands of a node, `!` keeps its single one on either side
+fn operands(tree: &BTree) -> (Option<&BTree>, Option<&BTree>) {
+    match (tree.c1.as_deref(), tree.c2.as_deref()) {
+        (None, Some(c2)) => (Some(c2), None),
+        operands => operands
+    }
+}
+
+/* ---------- GRAPH ---------- */
+fn dot_escape(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn reaches(edges: &[(char, char, String)], from: char, to: char) -> bool {
+    let mut seen: HashSet<char> = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(current) = stack.pop() {
+        if current == to {
+            return true
+        }
+        if seen.insert(current) {
+            stack.extend(edges.iter().filter(|(input, _, _)| *input == current).map(|(_, output, _)| *output));
+        }
+    }
+    false
+}
+
+// An edge from each premise to each conclusion of every rule, with the rule
+fn rule_edges(variables: &HashMap<char, Variable>) -> Vec<(char, char, String)> {
+    let mut edges: Vec<(char, char, String)> = Vec::new();
+    for rule in unique_rules(variables) {
+        let (inputs, outputs) = rule_variables(&rule);
+        for input in inputs.iter() {
+            for output in outputs.iter() {
+                edges.push((*input, *output, String::from(rule.formula_string.trim())));
+            }
+        }
+    }
+    edges
+}
+
+pub(crate) fn to_dot(variables: &HashMap<char, Variable>) -> String {
+    let edges = rule_edges(variables);
+    // an edge is part of a cycle when its conclusion leads back to its premise
+    let cyclic: Vec<bool> = edges.iter().map(|(input, output, _)| reaches(&edges, *output, *input)).collect();
+    let mut keys: Vec<&char> = variables.keys().collect();
+    keys.sort();
+    let mut dot = String::from("digraph rules {\n    rankdir=LR;\n    node [shape=box];\n");
+    for key in keys {
+        let variable = &variables[key];
+        let label = match &variable.alias_true {
+            Some(alias) => format!("{key}\\n{}", dot_escape(alias)),
+            None => key.to_string()
+        };
+        let in_cycle = edges.iter().zip(cyclic.iter()).any(|((input, output, _), c)| *c && (input == key || output == key));
+        let style = match (in_cycle, variable.requested) {
+            (true, _) => ", color=red, fontcolor=red",
+            (false, true) => ", style=bold",
+            _ => ""
+        };
+        dot.push_str(&format!("    \"{key}\" [label=\"{label}\"{style}];\n"));
+    }
+    for ((input, output, formula), c) in edges.iter().zip(cyclic.iter()) {
+        let style = match c {
+            true => ", color=red, fontcolor=red, penwidth=2",
+            false => ""
+        };
+        dot.push_str(&format!("    \"{input}\" -> \"{output}\" [label=\"{}\"{style}];\n", dot_escape(formula)));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/* ---------- JSON ---------- */
+// Every variable with its state, aliases and rules, sorted by letter
+pub(crate) fn to_json(variables: &HashMap<char, Variable>) -> String {
+    let sorted: BTreeMap<&char, &Variable> = variables.iter().collect();
+    serde_json::to_string_pretty(&sorted).map(|json| json + "\n").unwrap_or_default()
+}
+
+// A file written by hand is checked as the parser would have built it, the engine expects
+// every letter of a rule to exist, unique aliases, conclusions made of `&` only and each rule
+// under each of its conclusions
+pub(crate) fn from_json(contents: &str) -> Result<HashMap<char, Variable>, Error> {
+    let variables: HashMap<char, Variable> = serde_json::from_str(contents).map_err(|e| Error::InvalidJson(e.to_string()))?;
+    let mut aliases: HashMap<&String, char> = HashMap::new();
+    for key in sorted_keys(&variables) {
+        let variable = &variables[&key];
+        for alias in [&variable.alias_true, &variable.alias_false].into_iter().flatten() {
+            if let Some(other) = aliases.insert(alias, key) {
+                Err(Error::AlreadyAlias(alias.clone(), other.to_string()))?
+            }
+        }
+        for rule in variable.rules.iter() {
+            let formula = String::from(rule.formula_string.trim());
+            let (inputs, outputs) = rule_variables(rule);
+            if let Some(unknown) = inputs.iter().chain(outputs.iter()).find(|letter| !variables.contains_key(letter)) {
+                Err(Error::UnknownRuleVariable(unknown.to_string(), formula.clone()))?
+            }
+            if conclusions(&rule.output).is_none() {
+                Err(Error::OutputOperators)?
+            }
+            if !outputs.contains(&key) {
+                Err(Error::RuleOutsideOutput(formula.clone(), key.to_string()))?
+            }
+            if let Some(output) = outputs.iter().find(|output| !variables[output].rules.iter().any(|r| r.formula_string == rule.formula_string)) {
+                Err(Error::RuleMissingUnder(formula, output.to_string()))?
+            }
+        }
+    }
+    Ok(variables)
+}
+
+/* ---------- DIMACS ---------- */
+struct Cnf {
+    count: i64,
+    clauses: Vec<Vec<i64>>
+}
+
+impl Cnf {
+    fn fresh(&mut self) -> i64 {
+        self.count += 1;
+        self.count
+    }
+
+    // Tseitin encoding: each operator gets a variable equivalent to its result
+    fn literal(&mut self, tree: &BTree, numbers: &HashMap<char, i64>) -> Result<i64, Error> {
+        let (a, b) = match operands(tree) {
+            (Some(c1), c2) if !matches!(tree.node, Operator::Var(_) | Operator::B(_)) => (self.literal(c1, numbers)?, match c2 {
+                Some(c2) => self.literal(c2, numbers)?,
+                None => 0
+            }),
+            _ => (0, 0)
+        };
+        let t = match tree.node {
+            Operator::Var(v) => return numbers.get(&v).copied().ok_or(Error::CannotFindVariable(v.to_string())),
+            Operator::Not => return Ok(-a),
+            _ => self.fresh()
+        };
+        let clauses = match tree.node {
+            Operator::B(true) => vec![vec![t]],
+            Operator::B(false) => vec![vec![-t]],
+            Operator::And => vec![vec![-t, a], vec![-t, b], vec![t, -a, -b]],
+            Operator::Or => vec![vec![t, -a], vec![t, -b], vec![-t, a, b]],
+            Operator::Xor => vec![vec![-t, a, b], vec![-t, -a, -b], vec![t, -a, b], vec![t, a, -b]],
+            Operator::Equal => vec![vec![t, a, b], vec![t, -a, -b], vec![-t, -a, b], vec![-t, a, -b]],
+            Operator::Material => vec![vec![t, a], vec![t, -b], vec![-t, -a, b]],
+            _ => Vec::new()
+        };
+        self.clauses.extend(clauses);
+        Ok(t)
+    }
+}
+
+// Each rule is the clause `!input | output` over the variables of its sides, each fact a unit clause.
+// Rules alone let a variable be true without reason, so the Clark completion adds `!v | input...`
+// over the rules concluding each variable that is not a fact: as in the engine, a variable no rule
+// concludes is false. Comments map the letters to their numbers, the following ones are the operators
+pub(crate) fn to_dimacs(variables: &HashMap<char, Variable>) -> Result<String, Error> {
+    let keys = sorted_keys(variables);
+    let numbers: HashMap<char, i64> = keys.iter().enumerate().map(|(i, key)| (*key, i as i64 + 1)).collect();
+    let mut cnf = Cnf { count: keys.len() as i64, clauses: Vec::new() };
+    let mut comments: Vec<String> = keys.iter().map(|key| format!("c {} {key}{}", numbers[key], aliases(&variables[key]))).collect();
+    let mut inputs: HashMap<String, i64> = HashMap::new();
+    for rule in unique_rules(variables) {
+        let input = cnf.literal(&rule.input, &numbers)?;
+        let output = cnf.literal(&rule.output, &numbers)?;
+        comments.push(format!("c rule {}", rule.formula_string.trim()));
+        cnf.clauses.push(vec![-input, output]);
+        inputs.insert(rule.formula_string.clone(), input);
+    }
+    for key in keys.iter() {
+        let variable = &variables[key];
+        cnf.clauses.push(match (variable.locked, variable.value) {
+            (true, true) => vec![numbers[key]],
+            (true, false) => vec![-numbers[key]],
+            _ => [vec![-numbers[key]], variable.rules.iter().filter_map(|rule| inputs.get(&rule.formula_string).copied()).collect()].concat()
+        });
+    }
+    comments.extend(keys.iter().filter(|key| variables[key].requested).map(|key| format!("c query {} {key}", numbers[key])));
+    let mut lines = comments;
+    lines.push(format!("p cnf {} {}", cnf.count, cnf.clauses.len()));
+    lines.extend(cnf.clauses.iter().map(|clause| format!("{} 0", clause.iter().map(|literal| literal.to_string()).collect::<Vec<String>>().join(" "))));
+    Ok(lines.iter().map(|line| format!("{line}\n")).collect())
+}
+
+/* ---------- SMT-LIB ---------- */
+// Letters outside ascii are quoted to stay symbols
+fn smt_symbol(key: char) -> String {
+    match key.is_ascii_alphabetic() {
+        true => key.to_string(),
+        false => format!("|{key}|")
+    }
+}
+
+fn smt_term(tree: &BTree) -> String {
+    let operator = match tree.node {
+        Operator::Var(v) => return smt_symbol(v),
+        Operator::B(b) => return b.to_string(),
+        Operator::And => "and",
+        Operator::Or => "or",
+        Operator::Xor => "xor",
+        Operator::Equal => "=",
+        Operator::Material => "=>",
+        _ => "not"
+    };
+    match operands(tree) {
+        (Some(a), Some(b)) => format!("({operator} {} {})", smt_term(a), smt_term(b)),
+        (Some(a), None) => format!("({operator} {})", smt_term(a)),
+        _ => String::from("false")
+    }
+}
+
+// The rules, the facts and the Clark completion of `to_dimacs`, then each query is checked both ways:
+// unsat without it means it is true, unsat with it means it is false
+pub(crate) fn to_smt2(variables: &HashMap<char, Variable>) -> String {
+    let keys = sorted_keys(variables);
+    let mut smt = String::from("(set-logic QF_UF)\n");
+    for key in keys.iter() {
+        let comment = aliases(&variables[key]);
+        let comment = match comment.is_empty() {
+            true => String::new(),
+            false => format!(" ;{comment}")
+        };
+        smt.push_str(&format!("(declare-const {} Bool){comment}\n", smt_symbol(*key)));
+    }
+    for rule in unique_rules(variables) {
+        smt.push_str(&format!("(assert (=> {} {})) ; {}\n", smt_term(&rule.input), smt_term(&rule.output), rule.formula_string.trim()));
+    }
+    for key in keys.iter() {
+        let variable = &variables[key];
+        let symbol = smt_symbol(*key);
+        let bodies: Vec<String> = variable.rules.iter().map(|rule| smt_term(&rule.input)).collect();
+        smt.push_str(&match (variable.locked, variable.value, bodies.len()) {
+            (true, true, _) => format!("(assert {symbol})\n"),
+            (true, false, _) | (false, _, 0) => format!("(assert (not {symbol}))\n"),
+            (false, _, 1) => format!("(assert (=> {symbol} {}))\n", bodies[0]),
+            _ => format!("(assert (=> {symbol} (or {})))\n", bodies.join(" "))
+        });
+    }
+    for key in keys.iter().filter(|key| variables[key].requested) {
+        let symbol = smt_symbol(*key);
+        smt.push_str(&format!("; ?{key} is true when unsat\n(push 1)\n(assert (not {symbol}))\n(check-sat)\n(pop 1)\n"));
+        smt.push_str(&format!("; ?{key} is false when unsat\n(push 1)\n(assert {symbol})\n(check-sat)\n(pop 1)\n"));
+    }
+    smt
+}
+
+/* ---------- PROLOG ---------- */
+type Conjunction = Vec<(char, bool)>;
+
+fn cross(left: Vec<Conjunction>, right: Vec<Conjunction>) -> Vec<Conjunction> {
+    left.iter().flat_map(|l| right.iter().map(move |r| l.iter().chain(r.iter()).copied().collect())).collect()
+}
+
+// The formula, or its negation, as a disjunction of conjunctions of literals
+fn dnf(tree: &BTree, positive: bool) -> Vec<Conjunction> {
+    let both = |a: &BTree, b: &BTree, sign_a: bool, sign_b: bool| cross(dnf(a, sign_a), dnf(b, sign_b));
+    match (&tree.node, operands(tree), positive) {
+        (Operator::Var(v), _, _) => vec![vec![(*v, positive)]],
+        (Operator::B(b), _, _) if *b == positive => vec![Vec::new()],
+        (Operator::Not, (Some(a), _), _) => dnf(a, !positive),
+        (Operator::And, (Some(a), Some(b)), true) | (Operator::Or, (Some(a), Some(b)), false) => both(a, b, positive, positive),
+        (Operator::And | Operator::Or, (Some(a), Some(b)), _) => [dnf(a, positive), dnf(b, positive)].concat(),
+        (Operator::Material, (Some(a), Some(b)), true) => [dnf(a, false), dnf(b, true)].concat(),
+        (Operator::Material, (Some(a), Some(b)), false) => both(a, b, true, false),
+        (Operator::Xor, (Some(a), Some(b)), true) | (Operator::Equal, (Some(a), Some(b)), false) => [both(a, b, true, false), both(a, b, false, true)].concat(),
+        (Operator::Xor | Operator::Equal, (Some(a), Some(b)), _) => [both(a, b, true, true), both(a, b, false, false)].concat(),
+        _ => Vec::new()
+    }
+}
+
+// The variables of a conclusion made of `&` only
+fn conclusions(tree: &BTree) -> Option<Vec<char>> {
+    match (&tree.node, operands(tree)) {
+        (Operator::Var(v), _) => Some(vec![*v]),
+        (Operator::And, (Some(a), Some(b))) => Some([conclusions(a)?, conclusions(b)?].concat()),
+        _ => None
+    }
+}
+
+// Lowercase letters are atoms, other letters are quoted
+fn atom(key: char, variables: &HashMap<char, Variable>) -> String {
+    let lower = key.to_lowercase().next().unwrap_or(key);
+    match key.is_ascii_alphabetic() && (lower == key || !variables.contains_key(&lower)) {
+        true => lower.to_string(),
+        false => format!("'{}'", key.to_string().replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+fn literal(key: char, positive: bool, variables: &HashMap<char, Variable>) -> String {
+    match positive {
+        true => atom(key, variables),
+        false => format!("\\+ {}", atom(key, variables))
+    }
+}
+
+fn predicates(keys: &[char], variables: &HashMap<char, Variable>) -> String {
+    keys.iter().map(|key| format!("{}/0", atom(*key, variables))).collect::<Vec<String>>().join(", ")
+}
+
+// Each rule gives a clause per conclusion and per alternative of its premise, the parser and
+// `import json` only allow conjunctions as conclusions. Negations are written as negation as
+// failure, which is how the engine reads them. Atoms of a loop are tabled so that a query
+// terminates, the others are dynamic so that an unknown one fails
+pub(crate) fn to_prolog(variables: &HashMap<char, Variable>) -> String {
+    let keys = sorted_keys(variables);
+    let edges = rule_edges(variables);
+    let (looped, others): (Vec<char>, Vec<char>) = keys.iter().partition(|key| edges.iter().any(|(input, output, _)| input == *key && reaches(&edges, *output, **key)));
+    let mut lines: Vec<String> = keys.iter()
+        .filter(|key| variables[key].alias_true.is_some() || variables[key].alias_false.is_some())
+        .map(|key| format!("% {}{}", atom(*key, variables), aliases(&variables[key])))
+        .collect();
+    if !looped.is_empty() {
+        lines.push(format!(":- table {}.", predicates(&looped, variables)));
+    }
+    if !others.is_empty() {
+        lines.push(format!(":- dynamic {}.", predicates(&others, variables)));
+    }
+    lines.extend(keys.iter().filter(|key| variables[key].value && variables[key].locked).map(|key| format!("{}.", atom(*key, variables))));
+    for rule in unique_rules(variables) {
+        let formula = rule.formula_string.trim();
+        let alternatives = dnf(&rule.input, true);
+        if alternatives.iter().flatten().any(|(_, positive)| !positive) {
+            lines.push(format!("% not Horn, negation: {formula}"));
+        }
+        for output in conclusions(&rule.output).unwrap_or_default() {
+            for alternative in alternatives.iter() {
+                let head = atom(output, variables);
+                lines.push(match alternative.is_empty() {
+                    true => format!("{head}."),
+                    false => format!("{head} :- {}.", alternative.iter().map(|(key, positive)| literal(*key, *positive, variables)).collect::<Vec<String>>().join(", "))
+                });
+            }
+        }
+    }
+    lines.extend(keys.iter().filter(|key| variables[key].requested).map(|key| format!("?- {}.", atom(*key, variables))));
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::fill_source;
+
+    fn load(rules: &str) -> HashMap<char, Variable> {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        fill_source(&mut variables, rules, true).unwrap();
+        variables
+    }
+
+    // Whether the clauses with the extra literal have a model, trying every assignment
+    fn satisfiable(dimacs: &str, extra: i64) -> bool {
+        let mut count = 0;
+        let mut clauses: Vec<Vec<i64>> = vec![vec![extra]];
+        for line in dimacs.lines().filter(|line| !line.starts_with('c')) {
+            match line.strip_prefix("p cnf ") {
+                Some(header) => count = header.split(' ').next().unwrap().parse().unwrap(),
+                None => clauses.push(line.split(' ').map(|literal| literal.parse().unwrap()).filter(|literal| *literal != 0).collect())
+            }
+        }
+        (0..1u32 << count).any(|model| clauses.iter().all(|clause| clause.iter().any(|literal| (model >> (literal.abs() - 1) & 1 == 1) == (*literal > 0))))
+    }
+
+    #[test]
+    fn graph_marks_cycles_and_queries() {
+        let dot = to_dot(&load("def A \"sun\"\nA => B\nB => A\nA => C\n?C\n"));
+        assert!(dot.contains("    \"A\" -> \"B\" [label=\"A => B\", color=red, fontcolor=red, penwidth=2];\n"));
+        assert!(dot.contains("    \"A\" -> \"C\" [label=\"A => C\"];\n"));
+        assert!(dot.contains("    \"C\" [label=\"C\", style=bold];\n"));
+        assert!(dot.contains("    \"A\" [label=\"A\\nsun\", color=red, fontcolor=red];\n"));
+    }
+
+    #[test]
+    fn dimacs_answers_as_the_engine() {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        crate::parsing::fill_maps(&mut variables, "test_files/good/fritz.ru", true).unwrap();
+        let dimacs = to_dimacs(&variables).unwrap();
+        let number = |key: &str| dimacs.lines().find_map(|line| line.strip_prefix("c ").and_then(|line| line.split_once(' ')).filter(|(_, rest)| rest.starts_with(key)).map(|(n, _)| n.parse::<i64>().unwrap())).unwrap();
+        assert!(!satisfiable(&dimacs, -number("G ")));
+        assert!(!satisfiable(&dimacs, number("Y ")));
+    }
+
+    #[test]
+    fn smt2_completes_the_rules_and_checks_each_query_twice() {
+        let smt = to_smt2(&load("A | B => C\nD => C\n=A\n?C\n"));
+        assert!(smt.contains("(assert (=> (or A B) C)) ; A | B => C\n"));
+        assert!(smt.contains("(assert A)\n(assert (not B))\n(assert (=> C (or (or A B) D)))\n(assert (not D))\n"));
+        assert_eq!(smt.matches("(check-sat)").count(), 2);
+    }
+
+    #[test]
+    fn unknown_letters_are_errors() {
+        let mut variables = load("A => B\n");
+        variables.remove(&'A');
+        assert!(matches!(to_dimacs(&variables), Err(Error::CannotFindVariable(..))));
+    }
+
+    #[test]
+    fn prolog_tables_loops() {
+        let prolog = to_prolog(&load("A => B\nB + D => C\nC => A\n=D\n?A\n"));
+        assert_eq!(prolog.lines().take(3).collect::<Vec<&str>>(), vec![":- table a/0, b/0, c/0.", ":- dynamic d/0.", "d."]);
+        assert!(prolog.contains("c :- b, d.\n") && prolog.ends_with("?- a.\n"));
+    }
+
+    #[test]
+    fn json_round_trip_is_identical() {
+        let variables = load("def R \"It rains\" \"It's dry\"\nR & !C => W & U\nW | C => G\n=R\n?G\n");
+        let json = to_json(&variables);
+        assert_eq!(to_json(&from_json(&json).unwrap()), json);
+    }
+
+    #[test]
+    fn json_is_checked_on_import() {
+        let json = to_json(&load("A & B => C & D\n"));
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("A");
+        assert!(matches!(from_json(&value.to_string()), Err(Error::UnknownRuleVariable(..))));
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["B"]["rules"] = value["C"]["rules"].clone();
+        assert!(matches!(from_json(&value.to_string()), Err(Error::RuleOutsideOutput(..))));
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["D"]["rules"] = serde_json::json!([]);
+        assert!(matches!(from_json(&value.to_string()), Err(Error::RuleMissingUnder(..))));
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["A"]["alias_true"] = serde_json::json!("same");
+        value["B"]["alias_true"] = serde_json::json!("same");
+        assert!(matches!(from_json(&value.to_string()), Err(Error::AlreadyAlias(..))));
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["C"]["rules"][0]["output"]["node"] = serde_json::json!("Or");
+        value["D"]["rules"] = value["C"]["rules"].clone();
+        assert!(matches!(from_json(&value.to_string()), Err(Error::OutputOperators)));
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use colored::control::set_override;
+use crate::models::{Variable, Answer, ExitStatus};
+use crate::parsing::{fill_source, set_facts, find_named};
+use crate::algo::search_query;
+use crate::utils::history_text;
+use crate::translate::{Error, Lang};
+
+/// Answer of a query that could not be made, see `es_last_error`
+pub const ES_ERROR: c_int = -1;
+pub const ES_FALSE: c_int = 0;
+pub const ES_TRUE: c_int = 1;
+pub const ES_UNDETERMINED: c_int = 2;
+
+/// A knowledge base, only handled through pointers
+pub struct EsKnowledgeBase {
+    variables: HashMap<char, Variable>,
+    error: CString
+}
+
+/* ---------- HELPERS ---------- */
+impl EsKnowledgeBase {
+    fn fail(&mut self, error: Error, status: ExitStatus) -> c_int {
+        self.error = CString::new(error.to_string().replace('\0', "")).unwrap_or_default();
+        status as c_int
+    }
+}
+
+// Runs a call with the error of the previous one cleared. A panic of the engine must not unwind
+// into C, it is reported as an unexpected error and the call returns `failed`
+unsafe fn call<T>(kb: *mut EsKnowledgeBase, failed: T, body: impl FnOnce(&mut EsKnowledgeBase) -> T) -> T {
+    let Some(kb) = kb.as_mut() else { return failed };
+    kb.error = CString::default();
+    match catch_unwind(AssertUnwindSafe(|| body(&mut *kb))) {
+        Ok(result) => result,
+        Err(_) => {
+            kb.fail(Error::Unexpected, ExitStatus::Unexpected);
+            failed
+        }
+    }
+}
+
+// Null or invalid UTF-8 strings are refused with the message of the error
+unsafe fn to_str<'a>(string: *const c_char, name: &str) -> Result<&'a str, Error> {
+    match string.is_null() {
+        true => Err(Error::NotString(String::from(name))),
+        false => CStr::from_ptr(string).to_str().map_err(|_| Error::NotString(String::from(name)))
+    }
+}
+
+// The answer to a variable or an alias, evaluated on a copy so that a query changes nothing
+fn answer(variables: &HashMap<char, Variable>, name: &str) -> Result<(Answer, String), Error> {
+    let (key, negated) = find_named(variables, name).ok_or(Error::CannotFindVariable(name.to_string()))?;
+    let mut queried = variables.clone();
+    let (answer, trace) = match search_query(key, &mut queried, &mut Vec::new(), String::new()) {
+        Ok((true, history)) => (Answer::True, history_text(history, &queried, key, &Lang::current())),
+        Ok((false, history)) => (Answer::False, history_text(history, &queried, key, &Lang::current())),
+        Err(e) => (Answer::Undetermined, e.to_string())
+    };
+    match negated {
+        true => Ok((answer.negate(), trace)),
+        false => Ok((answer, trace))
+    }
+}
+
+/* ---------- API ---------- */
+/// Creates an empty knowledge base, to free with `es_free`. Texts of the engine are never colored
+#[no_mangle]
+pub extern "C" fn es_new() -> *mut EsKnowledgeBase {
+    catch_unwind(|| {
+        set_override(false);
+        Box::into_raw(Box::new(EsKnowledgeBase { variables: HashMap::new(), error: CString::default() }))
+    }).unwrap_or(null_mut())
+}
+
+/// Frees a knowledge base, null is ignored
+///
+/// # Safety
+/// `kb` comes from `es_new` and is not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn es_free(kb: *mut EsKnowledgeBase) {
+    if !kb.is_null() {
+        drop(catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(kb)))));
+    }
+}
+
+/// Adds the rules, facts and queries of a rule file's text. Returns 0, or the exit status of
+/// the command line for the error: 3 for a contradiction, 4 for a parse error, 5 for an unexpected
+/// error and 64 for a bad argument. The knowledge base is left as it was on error
+///
+/// # Safety
+/// `kb` comes from `es_new` and `rules` is a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn es_load(kb: *mut EsKnowledgeBase, rules: *const c_char) -> c_int {
+    call(kb, ExitStatus::Unexpected as c_int, |kb| {
+        let rules = match to_str(rules, "rules") {
+            Ok(rules) => rules,
+            Err(e) => return kb.fail(e, ExitStatus::Usage)
+        };
+        let mut variables = kb.variables.clone();
+        match fill_source(&mut variables, rules, true) {
+            Ok(_) => {
+                kb.variables = variables;
+                ExitStatus::AllTrue as c_int
+            },
+            Err(e) => {
+                let status = ExitStatus::from_error(&e);
+                kb.fail(e, status)
+            }
+        }
+    })
+}
+
+/// Sets a variable or an alias as a fact, a false alias sets its variable to false, or clears the fact. Returns 0, or 64 for an unknown name
+///
+/// # Safety
+/// `kb` comes from `es_new` and `name` is a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn es_set_fact(kb: *mut EsKnowledgeBase, name: *const c_char, value: bool) -> c_int {
+    call(kb, ExitStatus::Unexpected as c_int, |kb| {
+        match to_str(name, "name").and_then(|name| set_facts(&mut kb.variables, &[name.to_string()], value)) {
+            Ok(()) => ExitStatus::AllTrue as c_int,
+            Err(e) => kb.fail(e, ExitStatus::Usage)
+        }
+    })
+}
+
+/// Answers a variable or an alias: `ES_TRUE`, `ES_FALSE`, `ES_UNDETERMINED` or `ES_ERROR`
+///
+/// # Safety
+/// `kb` comes from `es_new` and `name` is a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn es_query(kb: *mut EsKnowledgeBase, name: *const c_char) -> c_int {
+    call(kb, ES_ERROR, |kb| {
+        match to_str(name, "name").and_then(|name| answer(&kb.variables, name)) {
+            Ok((Answer::True, _)) => ES_TRUE,
+            Ok((Answer::False, _)) => ES_FALSE,
+            Ok((Answer::Undetermined, _)) => ES_UNDETERMINED,
+            Err(e) => {
+                kb.fail(e, ExitStatus::Usage);
+                ES_ERROR
+            }
+        }
+    })
+}
+
+/// The trace that leads to the answer of a variable or an alias, as UTF-8 to free with
+/// `es_string_free`. Null on error, see `es_last_error`
+///
+/// # Safety
+/// `kb` comes from `es_new` and `name` is a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn es_explain(kb: *mut EsKnowledgeBase, name: *const c_char) -> *mut c_char {
+    call(kb, null_mut(), |kb| {
+        match to_str(name, "name").and_then(|name| answer(&kb.variables, name)) {
+            Ok((_, trace)) => CString::new(trace.replace('\0', "")).unwrap_or_default().into_raw(),
+            Err(e) => {
+                kb.fail(e, ExitStatus::Usage);
+                null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a string returned by `es_explain`, null is ignored
+///
+/// # Safety
+/// `string` comes from `es_explain` and is not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn es_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(catch_unwind(AssertUnwindSafe(|| drop(CString::from_raw(string)))));
+    }
+}
+
+/// The message of the error of the last call as UTF-8, empty when it succeeded. Owned by the
+/// knowledge base until its next call
+///
+/// # Safety
+/// `kb` comes from `es_new`
+#[no_mangle]
+pub unsafe extern "C" fn es_last_error(kb: *const EsKnowledgeBase) -> *const c_char {
+    match kb.as_ref() {
+        Some(kb) => kb.error.as_ptr(),
+        None => c"".as_ptr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::COLORS;
+
+    fn text(pointer: *const c_char) -> String {
+        unsafe { CStr::from_ptr(pointer) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn knowledge_base_is_queried_through_pointers() {
+        let _colors = COLORS.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            let kb = es_new();
+            assert_eq!(es_load(kb, c"def A \"rain\"\nA + B => C\n".as_ptr()), 0);
+            assert_eq!(es_query(kb, c"C".as_ptr()), ES_FALSE);
+            assert_eq!(es_set_fact(kb, c"rain".as_ptr(), true), 0);
+            assert_eq!(es_set_fact(kb, c"B".as_ptr(), true), 0);
+            assert_eq!(es_query(kb, c"C".as_ptr()), ES_TRUE);
+            let trace = es_explain(kb, c"C".as_ptr());
+            assert!(!trace.is_null() && !text(trace).is_empty());
+            es_string_free(trace);
+            es_free(kb);
+        }
+    }
+
+    #[test]
+    fn errors_last_one_call() {
+        let _colors = COLORS.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe {
+            let kb = es_new();
+            assert_eq!(es_load(kb, c"A + => C\n".as_ptr()), ExitStatus::ParseError as c_int);
+            assert_eq!(es_load(kb, c"A => C\n".as_ptr()), 0);
+            assert_eq!(es_query(kb, c"Z".as_ptr()), ES_ERROR);
+            assert!(!text(es_last_error(kb)).is_empty());
+            assert_eq!(es_query(kb, c"C".as_ptr()), ES_FALSE);
+            assert!(text(es_last_error(kb)).is_empty());
+            assert_eq!(es_query(kb, std::ptr::null()), ES_ERROR);
+            es_free(kb);
+        }
+    }
+
+    #[test]
+    fn panics_do_not_unwind_into_c() {
+        let _colors = COLORS.lock().unwrap_or_else(|e| e.into_inner());
+        let kb = es_new();
+        let status = unsafe { call(kb, ES_ERROR, |_| panic!("engine")) };
+        assert_eq!(status, ES_ERROR);
+        assert_eq!(text(unsafe { es_last_error(kb) }), Error::Unexpected.to_string());
+        unsafe { es_free(kb) };
+    }
+
+    #[cfg(feature = "header")]
+    #[test]
+    fn header_is_current() {
+        assert_eq!(include_str!(concat!(env!("OUT_DIR"), "/expert_system.h")), include_str!("../include/expert_system.h"));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fs::write;
+use colored::Colorize;
+use unicode_width::UnicodeWidthStr;
+use crate::models::{Variable, ExitStatus};
+use crate::parsing::{fill_source, continue_line};
+use crate::utils::read_source;
+use crate::translate::Error;
+
+const INDENT: &str = "    ";
+
+/* ---------- TOKENS ---------- */
+fn tokenize(code: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => (),
+            '"' => {
+                let mut quoted = String::from('"');
+                for c in chars.by_ref() {
+                    quoted.push(c);
+                    if c == '"' {
+                        break
+                    }
+                }
+                tokens.push(quoted);
+            },
+            '<' | '=' | '>' => {
+                let mut operator = String::from(c);
+                while let Some(next) = chars.next_if(|next| matches!(next, '<' | '=' | '>')) {
+                    operator.push(next);
+                }
+                tokens.push(operator);
+            },
+            '!' | '+' | '^' | '&' | '|' | '(' | ')' | '{' | '}' | '?' | '\\' => tokens.push(String::from(c)),
+            _ => {
+                let mut word = String::from(c);
+                while let Some(next) = chars.next_if(|next| !next.is_whitespace() && !"\"<=>!+^&|(){}?\\".contains(*next)) {
+                    word.push(next);
+                }
+                tokens.push(word);
+            }
+        }
+    }
+    tokens
+}
+
+// One space between tokens, none inside parentheses, after `!` or between a statement and its letters
+fn join_tokens(tokens: &[String]) -> String {
+    let mut line = String::new();
+    let mut statements = matches!(tokens.first().map(|t| t.as_str()), Some("=" | "?"));
+    let mut previous: Option<&str> = None;
+    for token in tokens {
+        let glued = match (previous, token.as_str()) {
+            (None, _) => true,
+            (Some("!" | "("), _) | (_, ")") => true,
+            (Some("=" | "?"), next) if statements && !next.starts_with('"') => true,
+            _ => false
+        };
+        if !glued {
+            line.push(' ');
+        }
+        line.push_str(token);
+        statements |= token == "{";
+        previous = Some(token);
+    }
+    line
+}
+
+/* ---------- LINES ---------- */
+// Comments start at the first `#`, as the parser reads them
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.split_once('#') {
+        Some((code, comment)) => (code, Some(comment)),
+        None => (line, None)
+    }
+}
+
+// Trailing comments of consecutive lines are aligned on the widest one, emojis take two columns
+fn align_comments(lines: Vec<(String, Option<String>)>) -> Vec<String> {
+    let mut formatted = Vec::new();
+    let mut block: Vec<(String, String)> = Vec::new();
+    let flush = |block: &mut Vec<(String, String)>, formatted: &mut Vec<String>| {
+        let width = block.iter().map(|(code, _)| code.width()).max().unwrap_or(0);
+        for (code, comment) in block.drain(..) {
+            formatted.push(format!("{code}{} #{comment}", " ".repeat(width - code.width())));
+        }
+    };
+    for (code, comment) in lines {
+        match comment {
+            Some(comment) if !code.trim().is_empty() => block.push((code, comment)),
+            comment => {
+                flush(&mut block, &mut formatted);
+                formatted.push(match comment {
+                    Some(comment) => format!("{code}#{comment}"),
+                    None => code
+                });
+            }
+        }
+    }
+    flush(&mut block, &mut formatted);
+    formatted
+}
+
+pub(crate) fn format_rules(contents: &str) -> String {
+    let mut lines: Vec<(String, Option<String>)> = Vec::new();
+    let mut depth = 0;
+    let mut pending = String::new();
+    for line in contents.lines() {
+        let (code, comment) = split_comment(line);
+        let tokens = tokenize(code);
+        if tokens.is_empty() && comment.is_none() {
+            pending.clear();
+            if lines.last().map(|(code, comment)| !code.is_empty() || comment.is_some()).unwrap_or(false) {
+                lines.push((String::new(), None));
+            }
+            continue
+        }
+        let continued = !pending.is_empty();
+        continue_line(&mut pending, code);
+        if tokens.first().map(|t| t.as_str()) == Some("}") {
+            depth = usize::max(depth, 1) - 1;
+        }
+        let indent = INDENT.repeat(depth + continued as usize);
+        let comment = comment.map(|comment| format!(" {}", comment.trim()).trim_end().to_string());
+        lines.push((format!("{indent}{}", join_tokens(&tokens)), comment));
+        if tokens.first().map(|t| t.as_str()) == Some("scenario") && tokens.last().map(|t| t.as_str()) == Some("{") {
+            depth += 1;
+        }
+    }
+    while lines.last().map(|(code, comment)| code.is_empty() && comment.is_none()).unwrap_or(false) {
+        lines.pop();
+    }
+    align_comments(lines).iter().map(|line| format!("{line}\n")).collect()
+}
+
+/* ---------- FMT ---------- */
+// Only files that parse are formatted, a broken rule is never rewritten. Stdin is formatted to stdout
+fn format_file(file: &str) -> Result<(String, String), Error> {
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    let contents = read_source(file)?;
+    fill_source(&mut variables, &contents, true)?;
+    let formatted = format_rules(&contents);
+    Ok((contents, formatted))
+}
+
+pub fn fmt_mode(files: &[String], check: bool) -> ExitStatus {
+    let mut status = ExitStatus::AllTrue;
+    for file in files {
+        match format_file(file) {
+            Ok((_, formatted)) if file == "-" && !check => print!("{formatted}"),
+            Ok((contents, formatted)) if contents == formatted => (),
+            Ok((_, _)) if check => {
+                println!("{file}");
+                status = status.max(ExitStatus::SomeFalse);
+            },
+            Ok((_, formatted)) => {
+                if let Err(e) = write(file, formatted) {
+                    eprintln!("{}", format!("{file}: {e}").red());
+                    status = status.max(ExitStatus::Usage);
+                }
+            },
+            Err(e) => {
+                eprintln!("{}", Error::in_file(file, e).to_string().red());
+                status = status.max(ExitStatus::ParseError);
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = "lang en\n\n\n=  A B # facts\nA+B=>  C#rule\n!( C | D )  ^ E =>F   #  exclusive\nscenario \"rain\" {\n=   W\n? C\n}\nA & \\\nB => G\n\n\n";
+
+    #[test]
+    fn formatting_spaces_tokens_and_aligns_comments() {
+        let formatted = format_rules(MESSY);
+        assert_eq!(formatted, "lang en\n\n=A B              # facts\nA + B => C        # rule\n!(C | D) ^ E => F # exclusive\nscenario \"rain\" {\n    =W\n    ?C\n}\nA & \\\n    B => G\n");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let formatted = format_rules(MESSY);
+        assert_eq!(format_rules(&formatted), formatted);
+        for entry in std::fs::read_dir("test_files/good").unwrap() {
+            let contents = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            let formatted = format_rules(&contents);
+            assert_eq!(format_rules(&formatted), formatted);
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_the_rules() {
+        let (mut before, mut after) = (HashMap::new(), HashMap::new());
+        fill_source(&mut before, MESSY, true).unwrap();
+        fill_source(&mut after, &format_rules(MESSY), true).unwrap();
+        let mut keys: Vec<&char> = before.keys().collect();
+        keys.sort();
+        for key in keys {
+            assert_eq!((before[key].value, before[key].requested, before[key].rules.len()), (after[key].value, after[key].requested, after[key].rules.len()));
+        }
+    }
+}
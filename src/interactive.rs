@@ -1,259 +1,599 @@
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::{Editor, Config, CompletionType};
 use colored::Colorize;
 use std::process::Command;
 use std::collections::HashMap;
-use crate::utils::{print_variables, print_rules, tick_or_cross, print_variable};
-use crate::parsing::{fill_maps, parse_line};
-use crate::models::{Variable, Rule};
+use std::env::var;
+use std::fs::{write, read_to_string, create_dir_all, OpenOptions};
+use std::io::{Write, Read, stdin};
+use std::path::PathBuf;
+use std::mem::take;
+use crate::utils::{print_variables, print_rules, numbered_rules, tick_or_cross, print_variable, unknown, is_plain};
+use crate::parsing::{fill_maps, parse_line, continue_line, localize_line};
+use crate::models::{Variable, Rule, ExitStatus};
 use crate::algo::{algo_v1, search_query};
 use crate::leakser::Flag;
 use crate::utils::print_history;
-use crate::translate::{Lang, Translate};
+use crate::translate::{Lang, Translate, Error, available_langs};
+use crate::export::{export, format_named, format_names, from_json};
+use crate::prompt::{PromptHelper, signature};
+use crate::watch::{poll_changes, query_results, print_diff, Changes};
 
-pub fn interactive_mode(files: &Vec<String>, flags: &mut Vec<Flag>) {
-    let mut variables: HashMap<char, Variable> = HashMap::new();
-    let mut lang = match flags.iter().find(|flag| match flag {Flag::Lang(_) => true, _ => false}).unwrap_or(&Flag::Lang(Lang::En)) {
-        Flag::Lang(l) => l,
-        _ => unreachable!()
-    }.clone();
-    for file in files {
-        let mut new_variables = variables.clone();
-        if let Err(e) = fill_maps(&mut new_variables, file, true) {
-            println!("{}", format!(" - {file}: {e}").red());
-        } else {
-            println!("{}", format!(" + {file}").green());
-            variables = new_variables;
+pub(crate) struct Session {
+    pub variables: HashMap<char, Variable>,
+    pub lang: Lang,
+    pub flags: Vec<Flag>,
+    pub files: Vec<String>,
+    pub journal: Option<String>,
+    // commands that changed the knowledge base since the files were loaded
+    edits: Vec<String>,
+    watcher: Option<(Changes, Vec<(String, String)>)>
+}
+
+pub(crate) enum Execution {
+    Quit,
+    Status(bool),
+    Unchanged
+}
+
+impl Session {
+    pub fn new(files: &[String], flags: &[Flag]) -> Self {
+        let mut session = Session::loaded(files, HashMap::new(), flags);
+        session.load_files();
+        session
+    }
+
+    // A session on variables already loaded from the files, without printing them
+    pub(crate) fn loaded(files: &[String], variables: HashMap<char, Variable>, flags: &[Flag]) -> Self {
+        let lang = flags.iter().find_map(|flag| match flag {Flag::Lang(l) => Some(l.clone()), _ => None}).unwrap_or_default();
+        lang.set_current();
+        Session {
+            variables,
+            lang,
+            flags: flags.to_vec(),
+            files: files.to_vec(),
+            journal: None,
+            edits: Vec::new(),
+            watcher: None
+        }
+    }
+
+    fn load_files(&mut self) {
+        for file in self.files.iter() {
+            let mut new_variables = self.variables.clone();
+            if let Err(e) = fill_maps(&mut new_variables, file, true) {
+                println!("{}", format!(" - {}", Error::in_file(file, e).message(&self.lang)).red());
+            } else {
+                println!("{}", format!(" + {file}").green());
+                self.variables = new_variables;
+            }
+        }
+    }
+
+    // Reloads the files when one of them changed, then makes the interactive edits again on top of them.
+    // An edit the new files refuse is dropped with its error
+    fn reload_watched(&mut self) {
+        let changed = match &self.watcher {
+            Some((changed, _)) => changed.lock().map(|mut changed| take(&mut *changed)).unwrap_or_default(),
+            None => return
+        };
+        if changed.is_empty() {
+            return
+        }
+        self.variables.clear();
+        self.load_files();
+        for edit in take(&mut self.edits) {
+            if let Execution::Status(true) = execute(self, &edit) {
+                self.edits.push(edit);
+            }
+        }
+        let current = query_results(&mut self.variables.clone(), "");
+        if let Some((_, previous)) = &mut self.watcher {
+            print_diff(previous, &current);
+            *previous = current;
+        }
+    }
+
+    // Runs one command and records it in the journal when it changed the session
+    pub fn run(&mut self, line: &str) -> Execution {
+        self.reload_watched();
+        let execution = execute(self, line);
+        if !matches!(execution, Execution::Status(true)) || !is_mutating(line) {
+            return execution
+        }
+        if let Some(journal) = &self.journal {
+            let appended = OpenOptions::new().create(true).append(true).open(journal).and_then(|mut file| writeln!(file, "{}", line.trim()));
+            if let Err(e) = appended {
+                println!("{}", format!("{journal}: {e}").red());
+            }
         }
+        match first_word(line).as_deref() {
+            Some("reset") => self.edits.clear(),
+            Some("trace" | "lang" | "language") => (),
+            Some("file") => {
+                self.edits.push(String::from(line.trim()));
+                // the new file is watched too, by a poller over the longer list
+                if let Some((changes, _)) = &mut self.watcher {
+                    *changes = poll_changes(&watched_files(&self.files, &self.edits), &self.lang);
+                }
+            },
+            _ => self.edits.push(String::from(line.trim()))
+        }
+        execution
     }
-    let mut rl = Editor::<()>::new();
-    let mut status = "ø".yellow();
+}
+
+// The files of the command line then the ones loaded with `file` since the last reset
+fn watched_files(files: &[String], edits: &[String]) -> Vec<String> {
+    let mut watched = files.to_vec();
+    for edit in edits.iter().filter(|edit| first_word(edit).as_deref() == Some("file")) {
+        if let Some(file) = edit.split_whitespace().nth(1).map(String::from).filter(|file| !watched.contains(file)) {
+            watched.push(file);
+        }
+    }
+    watched
+}
+
+fn first_word(line: &str) -> Option<String> {
+    localize_line(line.trim(), &Lang::current(), false).to_lowercase().split_whitespace().next().map(String::from)
+}
+
+fn is_mutating(line: &str) -> bool {
+    match first_word(line).as_deref() {
+        Some("reset" | "clear" | "trace" | "lang" | "language" | "file" | "import" | "remove" | "del" | "delete" | "def" | "if") => true,
+        Some(first) => first.starts_with('=') || first.starts_with('?'),
+        None => false
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    let data_dir = match (var("XDG_DATA_HOME"), var("HOME"), var("APPDATA")) {
+        (Ok(xdg), _, _) if !xdg.is_empty() => PathBuf::from(xdg),
+        (_, Ok(home), _) => PathBuf::from(home).join(".local").join("share"),
+        (_, _, Ok(appdata)) => PathBuf::from(appdata),
+        _ => return None
+    }.join("expert-system");
+    create_dir_all(&data_dir).ok()?;
+    Some(data_dir.join("history"))
+}
+
+pub fn interactive_mode(files: &[String], flags: &mut Vec<Flag>) {
+    let mut session = Session::new(files, flags);
+    let mut rl = Editor::<PromptHelper>::with_config(Config::builder().completion_type(CompletionType::List).build());
+    rl.set_helper(Some(PromptHelper::new(&session.variables)));
+    let history = history_file();
+    if let Some(history) = &history {
+        drop(rl.load_history(history));
+    }
+    let prompt = session.flags.iter().find_map(|flag| match flag {Flag::Prompt(p) => Some(p.clone()), _ => None}).unwrap_or(String::from("[expert-system]"));
+    let mut status = unknown();
+    // words need a space before the prompt, symbols are stuck to it
+    let separator = match is_plain() {
+        true => " ",
+        false => ""
+    };
+    let mut pending = String::new();
     loop {
-        let readline = rl.readline(&format!("{status}[expert-system] "));
-        match readline {
+        if let Some(helper) = rl.helper_mut() {
+            helper.set_variables(&session.variables);
+        }
+        let shown = match pending.is_empty() {
+            true => format!("{status}{separator}{prompt} "),
+            false => format!("{}", format!("{:<1$}", " ...", prompt.chars().count() + 2).dimmed())
+        };
+        match rl.readline(&shown) {
             Ok(line) => {
+                // a continued command is recalled whole, not line by line
+                let line = match continue_line(&mut pending, &line) {
+                    Some(line) => line,
+                    None => continue
+                };
                 rl.add_history_entry(line.as_str());
-                let mut lower_line = line.to_lowercase();
-                lower_line = lower_line.trim().to_string();
-                match lower_line.as_str() {
-                    "quit" => break,
-                    "reset" => {
-                        variables.clear();
-                        for file in files {
-                            let mut new_variables = variables.clone();
-                            if let Err(e) = fill_maps(&mut new_variables, file, true) {
-                                println!("{}", format!(" - {file}: {e}").red());
-                            } else {
-                                println!("{}", format!(" + {file}").green());
-                                variables = new_variables;
+                match session.run(&line) {
+                    Execution::Quit => break,
+                    Execution::Status(success) => status = tick_or_cross(success),
+                    Execution::Unchanged => ()
+                }
+            },
+            Err(ReadlineError::Interrupted) => break,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break
+            }
+        }
+    }
+    if let Some(history) = &history {
+        if let Err(e) = rl.save_history(history) {
+            println!("{}", format!("{}: {e}", history.display()).red());
+        }
+    }
+    *flags = session.flags;
+}
+
+// Runs each line of a script, `#` starts a comment, returns the failing commands with the line they start on
+fn run_lines(session: &mut Session, contents: &str, stop_on_error: bool) -> Vec<(usize, String)> {
+    let mut failures = Vec::new();
+    let mut pending = String::new();
+    let mut start = 1;
+    for (i, line) in contents.lines().chain([""]).enumerate() {
+        if pending.is_empty() {
+            start = i + 1;
+        }
+        let line = match continue_line(&mut pending, line.split('#').next().unwrap_or_default()) {
+            Some(line) if !line.is_empty() => line,
+            _ => continue
+        };
+        match session.run(&line) {
+            Execution::Status(false) => {
+                failures.push((start, line));
+                if stop_on_error {
+                    break
+                }
+            },
+            Execution::Quit => break,
+            _ => ()
+        }
+    }
+    failures
+}
+
+fn replay(session: &mut Session, file: &str) -> bool {
+    let contents = match read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("{}", format!("{file}: {e}").red());
+            return false
+        }
+    };
+    if let Some((i, line)) = run_lines(session, &contents, true).first() {
+        println!("{}", Error::ReplayStopped(String::from(file), i.to_string(), line.clone()).message(&session.lang).red());
+        return false
+    }
+    println!("{}", format!("+ {file}").green());
+    true
+}
+
+// Runs the commands of a script, or of stdin when the script is `-`, without a prompt
+pub fn script_mode(files: &[String], flags: &[Flag], script: &str) -> Result<ExitStatus, Error> {
+    if script == "-" && files.iter().any(|file| file == "-") {
+        Err(Error::StdinTwice)?
+    }
+    let contents = match script {
+        "-" => {
+            let mut contents = String::new();
+            stdin().read_to_string(&mut contents).map_err(|e| Error::Io(String::from("stdin"), e.to_string()))?;
+            contents
+        },
+        path => read_to_string(path).map_err(|e| Error::Io(String::from(path), e.to_string()))?
+    };
+    let stop_on_error = flags.contains(&Flag::StopOnError);
+    let mut session = Session::new(files, flags);
+    let failures = run_lines(&mut session, &contents, stop_on_error);
+    for (i, line) in failures.iter() {
+        eprintln!("{}", format!("{script}:{i}: {line}").red());
+    }
+    match failures.is_empty() {
+        true => Ok(ExitStatus::AllTrue),
+        false => Ok(ExitStatus::Unexpected)
+    }
+}
+
+pub(crate) fn execute(session: &mut Session, line: &str) -> Execution {
+    let line = String::from(line);
+    let variables = &mut session.variables;
+    let lang = &mut session.lang;
+    let flags = &mut session.flags;
+    let mut lower_line = line.to_lowercase();
+    lower_line = lower_line.trim().to_string();
+    let status = match lower_line.as_str() {
+        "quit" => return Execution::Quit,
+        "reset" => {
+            variables.clear();
+            session.load_files();
+            true
+        }
+        "trace" => {
+            match flags.contains(&Flag::Trace) {
+                true => {
+                    *flags = flags.iter().filter(|flag| *flag != &Flag::Trace).cloned().collect();
+                    println!("{}", "- trace".red());
+                },
+                _ => {
+                    flags.push(Flag::Trace);
+                    println!("{}", "+ trace".green());
+                }
+            }
+            true
+        },
+        "variables" | "var" => {
+            if variables.len() > 0 {
+                print_variables(variables);
+            } else {
+                println!("{}", Error::NoVariables.message(lang));
+            }
+            true
+        },
+        "rules" | "rule" => {
+            if variables.len() > 0 {
+                print_rules(variables);
+            } else {
+                println!("{}", Error::NoRules.message(lang));
+            }
+            true
+        },
+        "clear" => {
+            variables.clear();
+            true
+        },
+        _ => {
+            if let Some(variable) = variables.iter().find(|(k, v)| k.to_string() == line.clone() || v.alias_false == Some(line.clone()) || v.alias_true == Some(line.clone())) {
+                print_variable(variable);
+                return Execution::Status(true)
+            }
+            let chunks: Vec<&str> = line.split(" ").filter(|c| c.len() > 0).collect();
+            let key_word = match chunks.get(0) {
+                Some(key_word) => key_word,
+                None => return Execution::Unchanged
+            };
+            match key_word.to_lowercase().trim() {
+                "lang" | "language" => {
+                    if let Some(language) = chunks.get(1) {
+                        match Lang::from_code(language) {
+                            Ok(chosen) => {
+                                chosen.set_current();
+                                *lang = chosen
+                            },
+                            Err(_) => {
+                                println!("{}", Error::InvalidLanguage(language.to_string(), available_langs().join(", ")).message(lang).red());
+                                return Execution::Status(true)
                             }
                         }
-                        status = tick_or_cross(true);
+                        println!("{}", format!("+ {language}").green());
+                        true
+                    } else {
+                        println!("{}", Error::NoLanguage(available_langs().join(", ")).message(lang).red());
+                        false
                     }
-                    "trace" => {
-                        match flags.contains(&Flag::Trace) {
-                            true => {
-                                *flags = flags.iter().filter(|flag| *flag != &Flag::Trace).cloned().collect();
-                                println!("{}", "- trace".red());
-                            },
-                            _ => {
-                                flags.push(Flag::Trace);
-                                println!("{}", "+ trace".green());
+                }
+                "help" => {
+                    let mut iter_chunk = chunks.iter();
+                    iter_chunk.next();
+                    helper(lang, iter_chunk.collect())
+                },
+                "pwd" | "ls" => {
+                    let mut command = Command::new(key_word.to_lowercase().trim());
+                    let mut iter_chunk = chunks.iter();
+                    iter_chunk.next();
+                    for chunk in iter_chunk {
+                        command.arg(chunk);
+                    }
+                    command.status().expect("failed to execute process");
+                    true
+                },
+                "exec" | "run" | "execute" => {
+                    let mut iter_chunk = chunks.iter();
+                    let mut ret = true;
+                    iter_chunk.next();
+                    if iter_chunk.len() == 0 {
+                        algo_v1(variables, flags.contains(&Flag::Trace), lang);
+                        return Execution::Status(true)
+                    }
+                    for var in iter_chunk {
+                        let var_name = match variables.iter().find(|(k, v)| k.to_string() == *var || v.alias_false == Some(String::from(*var)) || v.alias_true == Some(String::from(*var))) {
+                            Some((k, _)) => *k,
+                            None => {
+                                println!("{}", Error::DoesNotExist(var.to_string()).message(lang).red());
+                                ret = false;
+                                continue
                             }
+                        };
+                        match search_query(var_name, variables, &mut Vec::new(), String::default()) {
+                            Ok((res, history)) => {
+                                if flags.contains(&Flag::Trace) {
+                                    print_history(history, variables, var_name, lang);
+                                } else {
+                                    println!("{} is {}", var_name, res);
+                                }
+                            },
+                            Err(e) => println!("{} => {}", var_name, e.message(lang))
                         }
-                        status = tick_or_cross(true);
-                    },
-                    "variables" | "var" => {
-                        if variables.len() > 0 {
-                            print_variables(&variables);
+                    }
+                    ret
+                },
+                "file" => {
+                    if let Some(file) = chunks.get(1) {
+                        if let Err(e) = fill_maps(variables, file, true) {
+                            println!("{}", e.message(lang).red());
+                            false
                         } else {
-                            println!("no variables set");
+                            true
                         }
-                        status = tick_or_cross(true);
-                    },
-                    "rules" | "rule" => {
-                        if variables.len() > 0 {
-                            print_rules(&variables);
-                        } else {
-                            println!("no rules set");
+                    } else {
+                        println!("{}", Error::NoFileProvided.message(lang).red());
+                        false
+                    }
+                }
+                "replay" => {
+                    match chunks.get(1) {
+                        Some(file) => replay(session, file),
+                        None => {
+                            println!("{}", Error::NoFileProvided.message(lang).red());
+                            false
                         }
-                        status = tick_or_cross(true);
-                    },
-                    "clear" => {
-                        variables.clear();
-                        status = tick_or_cross(true);
-                    },
-                    _ => {
-                        if let Some(variable) = variables.iter().find(|(k, v)| k.to_string() == line.clone() || v.alias_false == Some(line.clone()) || v.alias_true == Some(line.clone())) {
-                            print_variable(variable);  
-                            status = tick_or_cross(true);  
-                        } else {
-                            let chunks: Vec<&str> = line.split(" ").filter(|c| c.len() > 0).collect();
-                            if let Some(key_word) = chunks.get(0) {
-                                match key_word.to_lowercase().trim() {
-                                    "lang" | "language" => {
-                                        if let Some(language) = chunks.get(1) {
-                                            match language.to_lowercase().trim() {
-                                                "en" => lang = Lang::En,
-                                                "fr" => lang = Lang::Fr,
-                                                "it" => lang = Lang::It,
-                                                _ => {
-                                                    println!("{}", format!("{language} is not a valid language. try [En, Fr, It]").red());
-                                                    status = tick_or_cross(true);
-                                                    continue
-                                                },
-                                            }
-                                            println!("{}", format!("+ {language}").green());
-                                            status = tick_or_cross(true);
-                                        } else {
-                                            println!("{}","no language providen [En, Fr, It]".red());
-                                            status = tick_or_cross(false);
-                                        }
-                                    }
-                                    "help" => {
-                                        let mut iter_chunk = chunks.iter();
-                                        iter_chunk.next();
-                                        status = tick_or_cross(helper(&lang, iter_chunk.collect()));
-                                    },
-                                    "pwd" | "ls" => {
-                                        let mut command = Command::new(key_word.to_lowercase().trim());
-                                        let mut iter_chunk = chunks.iter();
-                                        iter_chunk.next();
-                                        for chunk in iter_chunk {
-                                            command.arg(chunk);
-                                        }
-                                        command.status().expect("failed to execute process");
-                                        status = tick_or_cross(true);
-                                    },
-                                    "exec" | "run" | "execute" => {
-                                        let mut iter_chunk = chunks.iter();
-                                        let mut ret = true;
-                                        iter_chunk.next();
-                                        if iter_chunk.len() == 0 {
-                                            algo_v1(&mut variables, flags.contains(&Flag::Trace), &lang);
-                                            status = tick_or_cross(true);
-                                            continue
-                                        }
-                                        for var in iter_chunk {
-                                            let var_name = match variables.iter().find(|(k, v)| k.to_string() == var.clone() || v.alias_false == Some(String::from(*var)) || v.alias_true == Some(String::from(*var))) {
-                                                Some((k, _)) => *k,
-                                                None => {
-                                                    println!("{}", format!("{var} does not exist").red());
-                                                    ret = false;
-                                                    continue
-                                                }
-                                            };
-                                            match search_query(var_name, &mut variables, &mut Vec::new(), String::default()) {
-                                                Ok((res, history)) => {
-                                                    if flags.contains(&Flag::Trace) {
-                                                        print_history(history, &variables, var_name, &lang);
-                                                    } else {
-                                                        println!("{} is {}", var_name, res);
-                                                    }
-                                                },
-                                                Err(e) => println!("{} => {}", var_name, e)
-                                            }
-                                        }
-                                        status = tick_or_cross(ret);
-                                    },
-                                    "file" => {
-                                        if let Some(file) = chunks.get(1) {
-                                            if let Err(e) = fill_maps(&mut variables, file, true) {
-                                                println!("{e}");
-                                                status = tick_or_cross(false);
-                                            } else {
-                                                status = tick_or_cross(true);
-                                            }
-                                        } else {
-                                            println!("{}", "no file provided".red());
-                                            status = tick_or_cross(false);
-                                        }
-                                    }
-                                    "remove" | "del" | "delete" => {
-                                        if let Some(kind) = chunks.get(1) {
-                                            match kind.to_lowercase().trim() {
-                                                "all" => {
-                                                    variables.clear();
-                                                    status = tick_or_cross(true);
-                                                    println!("Ok");
-                                                }
-                                                "set" | "=" => {
-                                                    let mut iter_chunk = chunks.iter();
-                                                    iter_chunk.next();
-                                                    iter_chunk.next();
-                                                    for chunk in iter_chunk {
-                                                        match variables.iter_mut().find(|(k, _)| (**k).to_string() == *chunk) {
-                                                            Some((k, var)) => {
-                                                                if var.locked || var.value {
-                                                                    println!("{}", format!("- {k}: {var}").red());
-                                                                    var.locked = false;
-                                                                    var.value = false;
-                                                                    println!("{}", format!("+ {k}: {var}").green());
-                                                                }
-                                                                status = tick_or_cross(true);
-                                                            }
-                                                            None => {
-                                                                println!("{}", format!("{chunk} does not exist").red());
-                                                                status = tick_or_cross(false);
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                                "request" | "req" | "?" => {
-                                                    let mut iter_chunk = chunks.iter();
-                                                    iter_chunk.next();
-                                                    iter_chunk.next();
-                                                    for chunk in iter_chunk {
-                                                        match variables.iter_mut().find(|(k, _)| (**k).to_string() == *chunk) {
-                                                            Some((k, var)) => {
-                                                                if var.requested {
-                                                                    println!("{}", format!("- {k}: {var}").red());
-                                                                    var.requested = false;
-                                                                    println!("{}", format!("+ {k}: {var}").green());
-                                                                }
-                                                                status = tick_or_cross(true);
-                                                            }
-                                                            None => {
-                                                                println!("{}", format!("{chunk} does not exist").red());
-                                                                status = tick_or_cross(false);
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                                "rule" | "rules" => status = tick_or_cross(remove_rule(chunks.get(2), &mut variables)),
-                                                "var" | "variable" => status = tick_or_cross(remove_variable(chunks.get(2), &mut variables)),
-                                                _ => {
-                                                    println!("Expected one of [rule, var, variable, all, ?, =] found {kind}");
-                                                    status = tick_or_cross(false);
-                                                }
-                                            }
-                                        }
-                                    },
-                                    _ => {
-                                        match parse_line(&mut variables, line, true, false) {
-                                            Ok(()) => {
-                                                status = tick_or_cross(true);
-                                            },
-                                            Err(e) => {
-                                                println!("{}", format!("{e}").red());
-                                                status = tick_or_cross(false);
-                                            }
-                                        }
-                                    }
+                    }
+                },
+                "journal" => {
+                    match chunks.get(1).map(|arg| arg.to_lowercase()) {
+                        Some(arg) if arg == "off" => {
+                            if let Some(journal) = session.journal.take() {
+                                println!("{}", format!("- {journal}").red());
+                            }
+                            true
+                        },
+                        Some(_) => {
+                            let journal = String::from(chunks[1]);
+                            println!("{}", format!("+ {journal}").green());
+                            session.journal = Some(journal);
+                            true
+                        },
+                        None => {
+                            match &session.journal {
+                                Some(journal) => println!("{journal}"),
+                                None => println!("{}", Error::NoJournal.message(lang))
+                            }
+                            true
+                        }
+                    }
+                },
+                "watch" => {
+                    match chunks.get(1).map(|arg| arg.to_lowercase()) {
+                        Some(arg) if arg == "on" => {
+                            let results = query_results(&mut variables.clone(), "");
+                            let files = watched_files(&session.files, &session.edits);
+                            session.watcher = Some((poll_changes(&files, lang), results));
+                            println!("{}", Translate::Watching.with(lang, &[files.join(", ")]).blue().bold());
+                            true
+                        },
+                        Some(arg) if arg == "off" => {
+                            session.watcher = None;
+                            true
+                        },
+                        _ => {
+                            println!("{}", Error::ExpectedOnOff.message(lang).red());
+                            false
+                        }
+                    }
+                },
+                "export" => {
+                    let content = match chunks.get(1).map(|kind| kind.to_lowercase()) {
+                        Some(kind) => match format_named(&kind) {
+                            Some(format) => match export(variables, format) {
+                                Ok(content) => content,
+                                Err(e) => {
+                                    println!("{}", e.message(lang).red());
+                                    return Execution::Status(false)
                                 }
+                            },
+                            None => {
+                                println!("{}", Error::ExpectedFormat(kind, format_names()).message(lang).red());
+                                return Execution::Status(false)
                             }
+                        },
+                        None => {
+                            println!("{}", Error::NoFormat(format_names()).message(lang).red());
+                            return Execution::Status(false)
                         }
-                    },
+                    };
+                    match chunks.get(2) {
+                        Some(path) => {
+                            if let Err(e) = write(path, content) {
+                                println!("{}", format!("{path}: {e}").red());
+                                false
+                            } else {
+                                println!("{}", format!("+ {path}").green());
+                                true
+                            }
+                        },
+                        None => {
+                            print!("{content}");
+                            true
+                        }
+                    }
+                },
+                "import" => {
+                    match (chunks.get(1).map(|kind| kind.to_lowercase()), chunks.get(2)) {
+                        (Some(kind), Some(path)) if kind == "json" => {
+                            match read_to_string(path).map_err(|e| Error::Io(path.to_string(), e.to_string())).and_then(|contents| from_json(&contents).map_err(|e| Error::in_file(path, e))) {
+                                Ok(imported) => {
+                                    *variables = imported;
+                                    println!("{}", format!("+ {path}").green());
+                                    true
+                                },
+                                Err(e) => {
+                                    println!("{}", e.message(lang).red());
+                                    false
+                                }
+                            }
+                        },
+                        (Some(kind), _) if kind != "json" => {
+                            println!("{}", Error::ExpectedFormat(kind, String::from("json")).message(lang).red());
+                            false
+                        },
+                        (Some(_), _) => {
+                            println!("{}", Error::NoFileProvided.message(lang).red());
+                            false
+                        },
+                        (None, _) => {
+                            println!("{}", Error::NoFormat(String::from("json")).message(lang).red());
+                            false
+                        }
+                    }
+                },
+                "remove" | "del" | "delete" => {
+                    let kind = match chunks.get(1) {
+                        Some(kind) => kind,
+                        None => return Execution::Unchanged
+                    };
+                    match kind.to_lowercase().trim() {
+                        "all" => {
+                            variables.clear();
+                            println!("Ok");
+                            true
+                        }
+                        "set" | "=" => return remove_state(&chunks[2..], variables, lang, |var| {
+                            let changed = var.locked || var.value;
+                            var.locked = false;
+                            var.value = false;
+                            changed
+                        }),
+                        "request" | "req" | "?" => return remove_state(&chunks[2..], variables, lang, |var| take(&mut var.requested)),
+                        "rule" | "rules" => remove_rule(chunks.get(2), variables, lang),
+                        "var" | "variable" => remove_variable(chunks.get(2), variables, lang),
+                        _ => {
+                            println!("{}", Error::ExpectedRemoveKind(kind.to_string()).message(lang));
+                            false
+                        }
+                    }
+                },
+                _ => {
+                    match parse_line(variables, localize_line(&line, lang, false), true, false) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            println!("{}", e.message(lang).red());
+                            false
+                        }
+                    }
                 }
-            },
-            Err(ReadlineError::Interrupted) => break,
-            Err(ReadlineError::Eof) => break,
-            Err(err) => {
-                println!("Error: {:?}", err);
-                break
+            }
+        },
+    };
+    Execution::Status(status)
+}
+
+// Clears a state of every named variable, or of none when one of them does not exist so that
+// a failing command, which is not journaled, changes nothing
+fn remove_state(names: &[&str], variables: &mut HashMap<char, Variable>, lang: &Lang, clear: impl Fn(&mut Variable) -> bool) -> Execution {
+    if names.is_empty() {
+        return Execution::Unchanged
+    }
+    let missing: Vec<&&str> = names.iter().filter(|name| !variables.keys().any(|k| k.to_string() == **name)).collect();
+    for name in missing.iter() {
+        println!("{}", Error::DoesNotExist(name.to_string()).message(lang).red());
+    }
+    if !missing.is_empty() {
+        return Execution::Status(false)
+    }
+    for name in names {
+        if let Some((k, var)) = variables.iter_mut().find(|(k, _)| k.to_string() == *name) {
+            let old = var.to_string();
+            if clear(var) {
+                println!("{}", format!("- {k}: {old}").red());
+                println!("{}", format!("+ {k}: {var}").green());
             }
         }
     }
+    Execution::Status(true)
 }
 
 fn rules_len(variables: &mut HashMap<char, Variable>) -> usize {
@@ -264,9 +604,9 @@ fn rules_len(variables: &mut HashMap<char, Variable>) -> usize {
     i
 }
 
-fn remove_rule(nb_s: Option<&&str>, variables: &mut HashMap<char, Variable>) -> bool {
+fn remove_rule(nb_s: Option<&&str>, variables: &mut HashMap<char, Variable>, lang: &Lang) -> bool {
     if rules_len(variables) == 0 {
-        println!("not any rules to delete");
+        println!("{}", Error::NoRuleToDelete.message(lang));
         return false
     }
     match nb_s {
@@ -281,41 +621,35 @@ fn remove_rule(nb_s: Option<&&str>, variables: &mut HashMap<char, Variable>) ->
             match nb_s.parse::<usize>() {
                 Ok(nb) => {
                     if nb <= 0 || nb > rules_len(variables) {
-                        println!("expected a number between 1 and {}", rules_len(variables));
+                        println!("{}", Error::ExpectedRuleIndex(rules_len(variables).to_string()).message(lang));
                         return false
                     }
-                    let mut i = 0;
-                    for (_, var) in variables.iter_mut() {
-                        let index = nb - 1 - i;
-                        if (index == 0 || index < var.rules.len()) && var.rules.len() > 0 {
-                            println!("{}", format!("- {}", var.rules.get(index).unwrap()).red());
-                            var.rules.remove(index);
-                            return true
-                        }
-                        i += var.rules.len();
-                    }
-                    false
+                    let (key, position) = numbered_rules(variables)[nb - 1];
+                    let rules = &mut variables.get_mut(&key).unwrap().rules;
+                    println!("{}", format!("- {}", rules[position]).red());
+                    rules.remove(position);
+                    true
                 },
                 Err(_) => {
-                    println!("{nb_s} is not a number");
+                    println!("{}", Error::NotANumber(nb_s.to_string()).message(lang));
                     false
                 }
             }
         },
         None => {
-            println!("expected a number found nothing");
+            println!("{}", Error::ExpectedNumber.message(lang));
             false
         }
     }
 }
 
-fn remove_variable(var_name: Option<&&str>, variables: &mut HashMap<char, Variable>) -> bool {
+fn remove_variable(var_name: Option<&&str>, variables: &mut HashMap<char, Variable>, lang: &Lang) -> bool {
     match var_name {
         Some(var_name) => {
             let key = match variables.iter().find(|(k, v)| k.to_string() == String::from(*var_name) || v.alias_false == Some(String::from(*var_name)) || v.alias_true == Some(String::from(*var_name))) {
                 Some((k, _)) => k.clone(),
                 None => {
-                    println!("cannot find variable {var_name}");
+                    println!("{}", Error::CannotFindVariable(var_name.to_string()).message(lang));
                     return false
                 }
             };
@@ -331,7 +665,7 @@ fn remove_variable(var_name: Option<&&str>, variables: &mut HashMap<char, Variab
             true
         },
         None => {
-            println!("expected a variable name found nothing");
+            println!("{}", Error::ExpectedVariableName.message(lang));
             false
         }
     }
@@ -341,34 +675,45 @@ fn remove_variable(var_name: Option<&&str>, variables: &mut HashMap<char, Variab
 fn helper(lang: &Lang, commands: Vec<&&str>) -> bool {
     let mut ret = true;
     if commands.len() == 0 {
-        return helper(lang, vec![&"help", &"quit", &"language", &"trace", &"reset", &"var", &"rule", &"clear", &"file", &"run", &"del", &"=", &"?", &"def", &"if"])
+        return helper(lang, vec![&"help", &"quit", &"language", &"trace", &"reset", &"var", &"rule", &"clear", &"file", &"run", &"del", &"=", &"?", &"def", &"if", &"export", &"import", &"journal", &"replay", &"watch"])
     }
     for (i, command) in commands.into_iter().enumerate() {
         if i > 0 {
             println!("");
         }
         match *command {
-            "help" => Translate::Help.print(lang, format!("{} {}", "help".blue().bold(), "<?Command ...>".purple().dimmed()), None),//display all commands or asked one"),
-            "lang" | "language" => Translate::HelpLanguage.print(lang, format!("{} {}", "help".blue().bold(), "[en, fr, it]".purple()), None),
+            "help" => Translate::Help.print(lang, format!("{} {}", "help".blue().bold(), signature("help").purple().dimmed()), None),//display all commands or asked one"),
+            "lang" | "language" => Translate::HelpLanguage.print(lang, format!("{} {}", "lang".blue().bold(), signature("lang").purple()), None),
             "trace" =>Translate::HelpTrace.print(lang, "trace".blue().bold(), None), //\n - unable/disable algorithm's trace"),
             "reset" =>Translate::HelpReset.print(lang, "reset".blue().bold(), None), //\n - clear the map and reload all providen files"),
             "quit" =>Translate::HelpQuit.print(lang, "quit".blue().bold(), None), //\n - quit the program"),
             "variables" | "var" =>Translate::HelpVariables.print(lang, "variables".blue().bold(), None), //\n - list all variables and their rules"),
             "rules" | "rule" =>Translate::HelpRules.print(lang, "rules".blue().bold(), None), //\n - list all rules"),
             "clear" =>Translate::HelpClear.print(lang, "clear".blue().bold(), None), //\n - alias for \"remove all\""),
-            "file" =>Translate::HelpFile.print(lang, format!("{} {}", "file".blue().bold(), "<Path>".purple()), None), //\n - read the file in path and enrich variables and rules"),
-            "exec" | "run" | "execute" =>Translate::HelpRun.print(lang, format!("{} {}", "run".blue().bold(), "<?Variable ...>".purple().dimmed()), None), //\n - run the algorithm with variable if providen"),
+            "file" =>Translate::HelpFile.print(lang, format!("{} {}", "file".blue().bold(), signature("file").purple()), None), //\n - read the file in path and enrich variables and rules"),
+            "exec" | "run" | "execute" =>Translate::HelpRun.print(lang, format!("{} {}", "run".blue().bold(), signature("run").purple().dimmed()), None), //\n - run the algorithm with variable if providen"),
             "remove" | "del" | "delete" => {
                Translate::HelpRemoveAll.print(lang, "remove all".blue().bold(), None); //\n - clear all variables and rules");
-               Translate::HelpRemoveVar.print(lang, format!("{} {}", "remove var".blue().bold(), " <Variable>".purple()), None); //\n - remove the variable and all rules implicated");
-               Translate::HelpRemoveRule.print(lang, format!("{} {}", "remove rule".blue().bold(), " <Index>".purple()), None); //\n - remove the rule depending the index listed with \"rules\"");
-               Translate::HelpRemoveRequest.print(lang, format!("{} {}", "remove ?".blue().bold(), " <?Variable ...>".purple().dimmed()), None); //\n - remove the variable from requested one");
-               Translate::HelpRemoveSet.print(lang, format!("{} {}", "remove =".blue().bold(), " <?Variable ...>".purple().dimmed()), None); //\n - remove the variable from seted one");
+               Translate::HelpRemoveVar.print(lang, format!("{} {}", "remove var".blue().bold(), signature("remove var").purple()), None); //\n - remove the variable and all rules implicated");
+               Translate::HelpRemoveRule.print(lang, format!("{} {}", "remove rule".blue().bold(), signature("remove rule").purple()), None); //\n - remove the rule depending the index listed with \"rules\"");
+               Translate::HelpRemoveRequest.print(lang, format!("{} {}", "remove ?".blue().bold(), signature("remove ?").purple().dimmed()), None); //\n - remove the variable from requested one");
+               Translate::HelpRemoveSet.print(lang, format!("{} {}", "remove =".blue().bold(), signature("remove =").purple().dimmed()), None); //\n - remove the variable from seted one");
+            },
+            "=" =>Translate::HelpSet.print(lang, format!("{} {}", "=".blue().bold(), signature("=").purple()), None), //\n - set the variable(s) to true"),
+            "?" =>Translate::HelpRequest.print(lang, format!("{} {}", "?".blue().bold(), signature("?").purple()), None), //\n - set the variable(s) to requested"),
+            "def" =>Translate::HelpDef.print(lang, format!("{} {}", "def".blue().bold(), signature("def").purple()), None), //\n - create a new variable with name \"Variable\""),
+            "if" =>Translate::HelpIf.print(lang, String::from(format!("{} {}", "if".blue().bold(), signature("if").purple())), None), //\n - create a new rule"),
+            "export" => {
+               Translate::HelpExportGraph.print(lang, format!("{} {}", "export graph".blue().bold(), signature("export graph").purple().dimmed()), None);
+               Translate::HelpExportJson.print(lang, format!("{} {}", "export json".blue().bold(), signature("export json").purple().dimmed()), None);
+               Translate::HelpExportDimacs.print(lang, format!("{} {}", "export dimacs".blue().bold(), signature("export dimacs").purple().dimmed()), None);
+               Translate::HelpExportSmt2.print(lang, format!("{} {}", "export smt2".blue().bold(), signature("export smt2").purple().dimmed()), None);
+               Translate::HelpExportProlog.print(lang, format!("{} {}", "export prolog".blue().bold(), signature("export prolog").purple().dimmed()), None);
             },
-            "=" =>Translate::HelpSet.print(lang, format!("{} {}", "=".blue().bold(), "<Variable ...>".purple()), None), //\n - set the variable(s) to true"),
-            "?" =>Translate::HelpRequest.print(lang, format!("{} {}", "?".blue().bold(), "<Variable ...>".purple()), None), //\n - set the variable(s) to requested"),
-            "def" =>Translate::HelpDef.print(lang, format!("{} {} {}", "def".blue().bold(), "<Variable>".purple(), "<?alias true> <?alias false>".purple().dimmed()), None), //\n - create a new variable with name \"Variable\""),
-            "if" =>Translate::HelpIf.print(lang, String::from(format!("{} {}", "if".blue().bold(), "<Rule>".purple())), None), //\n - create a new rule"),
+            "import" => Translate::HelpImportJson.print(lang, format!("{} {}", "import json".blue().bold(), signature("import json").purple()), None),
+            "journal" => Translate::HelpJournal.print(lang, format!("{} {}", "journal".blue().bold(), signature("journal").purple().dimmed()), None),
+            "replay" => Translate::HelpReplay.print(lang, format!("{} {}", "replay".blue().bold(), signature("replay").purple()), None),
+            "watch" => Translate::HelpWatch.print(lang, format!("{} {}", "watch".blue().bold(), signature("watch").purple()), None),
             "ls" | "pwd" => {
                 Command::new("man").arg(command).status().expect("failed to execute process");
             }
@@ -379,4 +724,92 @@ fn helper(lang: &Lang, commands: Vec<&&str>) -> bool {
         }
     }
     ret
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_states_is_all_or_nothing() {
+        let mut session = Session::new(&[], &[]);
+        run_lines(&mut session, "=AB\n?A\n", false);
+        assert!(matches!(session.run("remove set A Z"), Execution::Status(false)));
+        assert!(session.variables[&'A'].value && session.variables[&'B'].value);
+        assert!(matches!(session.run("remove ? A Z"), Execution::Status(false)));
+        assert!(session.variables[&'A'].requested);
+        assert!(matches!(session.run("remove set A B"), Execution::Status(true)));
+        assert!(!session.variables[&'A'].value && !session.variables[&'B'].locked);
+        assert!(matches!(session.run("remove ? A"), Execution::Status(true)));
+        assert!(!session.variables[&'A'].requested);
+    }
+
+    #[test]
+    fn failures_give_the_line_a_command_starts_on() {
+        let mut session = Session::new(&[], &[]);
+        let failures = run_lines(&mut session, "=A # fact\nremove set \\\n  Z\n\nA => (B\n  | C\n  + ) D\n", false);
+        assert_eq!(failures, vec![(2, String::from("remove set Z")), (5, String::from("A => (B | C + ) D"))]);
+    }
+
+    #[test]
+    fn stdin_is_not_both_map_and_script() {
+        assert!(matches!(script_mode(&[String::from("-")], &[], "-"), Err(Error::StdinTwice)));
+    }
+
+    #[test]
+    fn journals_keep_successful_edits_and_replay_them() {
+        let journal = std::env::temp_dir().join(format!("expert-system-journal-{}.esc", std::process::id())).display().to_string();
+        let mut session = Session::new(&[], &[]);
+        session.journal = Some(journal.clone());
+        run_lines(&mut session, "=A\nvariables\nremove set Z\nif A => B\n?B\n", false);
+        assert_eq!(read_to_string(&journal).unwrap(), "=A\nif A => B\n?B\n");
+        let mut replayed = Session::new(&[], &[]);
+        assert!(replay(&mut replayed, &journal));
+        assert!(replayed.variables[&'A'].value && replayed.variables[&'B'].requested);
+        std::fs::remove_file(&journal).unwrap();
+        assert!(!replay(&mut replayed, &journal));
+    }
+
+    #[test]
+    fn replayed_removals_take_the_same_rule() {
+        let journal = std::env::temp_dir().join(format!("expert-system-removal-{}.esc", std::process::id())).display().to_string();
+        let mut session = Session::new(&[], &[]);
+        session.journal = Some(journal.clone());
+        run_lines(&mut session, "if E => F\nif A => B\nif C => D\nremove rule 2\n", false);
+        for _ in 0..8 {
+            let mut replayed = Session::new(&[], &[]);
+            assert!(replay(&mut replayed, &journal));
+            assert!(replayed.variables[&'D'].rules.is_empty());
+            assert!(replayed.variables[&'B'].rules.len() == 1 && replayed.variables[&'F'].rules.len() == 1);
+        }
+        std::fs::remove_file(&journal).unwrap();
+    }
+
+    #[test]
+    fn scripts_remove_rules_by_their_listed_number() {
+        let script = std::env::temp_dir().join(format!("expert-system-script-{}.esc", std::process::id())).display().to_string();
+        std::fs::write(&script, "=A\nif A => Z\nif A => M\nif A => C\nremove rule 3\n?CMZ\n").unwrap();
+        assert_eq!(script_mode(&[], &[], &script).unwrap(), ExitStatus::AllTrue);
+        for _ in 0..8 {
+            let mut session = Session::new(&[], &[]);
+            assert!(run_lines(&mut session, &read_to_string(&script).unwrap(), true).is_empty());
+            assert!(session.variables[&'Z'].rules.is_empty());
+            assert!(session.variables[&'C'].rules.len() == 1 && session.variables[&'M'].rules.len() == 1);
+        }
+        std::fs::remove_file(&script).unwrap();
+    }
+
+    #[test]
+    fn files_loaded_in_the_session_are_watched() {
+        let file = std::env::temp_dir().join(format!("expert-system-loaded-{}.ru", std::process::id())).display().to_string();
+        std::fs::write(&file, "A => B\n?B\n").unwrap();
+        let mut session = Session::new(&[], &[]);
+        assert!(run_lines(&mut session, &format!("watch on\nfile {file}\n"), true).is_empty());
+        assert_eq!(watched_files(&session.files, &session.edits), vec![file.clone()]);
+        std::fs::write(&file, "=A\nA => B\n?B\n").unwrap();
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1200));
+        assert!(matches!(session.run("rules"), Execution::Status(true)));
+        assert!(session.variables[&'A'].value);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
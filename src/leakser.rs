@@ -1,76 +1,374 @@
 use std::env::args;
-use crate::utils::string_to_char;
-use crate::translate::Lang;
+use std::io::{stdin, stdout, IsTerminal};
+use std::process::exit;
+use clap::{Parser, Subcommand, Args, CommandFactory};
+use clap::error::ErrorKind;
+use clap_complete::{generate, Shell};
+use crate::translate::{Lang, Error};
+use crate::models::{Answer, ExitStatus};
+use crate::config::ColorMode;
+use crate::export::Format;
+use crate::serve::Address;
 
 #[derive(PartialEq, Clone)]
 pub enum Flag {
-    Help,
     Interactive,
     Trace,
+    NoTrace,
     Variables,
-    Lang(Lang)
+    Lang(Lang),
+    Test,
+    Batch,
+    Check,
+    Fmt { check: bool },
+    Export(Format),
+    Facts(String),
+    Output(String),
+    Script(String),
+    StopOnError,
+    Watch,
+    Color(ColorMode),
+    Plain,
+    NoPlain,
+    Prompt(String),
+    Completions(Shell),
+    Serve(Address),
+    Lsp,
+    Expect(Vec<(String, Answer)>),
+    Set(Vec<String>),
+    Unset(Vec<String>),
+    Query(Vec<String>)
 }
 
-impl Flag {
-    fn from_string(string: &str, iter: &mut std::slice::Iter<String>) -> Result<Self, String> {
-        Ok(match string {
-            "-h" | "--help" => Flag::Help,
-            "-i" | "--interactive" => Flag::Interactive,
-            "-t" | "--trace" => Flag::Trace,
-            "-v" | "--variables" => Flag::Variables,
-            "-l" | "--langage" => {
-                match iter.next() {
-                    Some(language) => {
-                        match language.as_str().to_lowercase().trim() {
-                            "en" => Flag::Lang(Lang::En),
-                            "fr" => Flag::Lang(Lang::Fr),
-                            "it" => Flag::Lang(Lang::It),
-                            _ => Err(format!("{language} is unknown, try [en, fr, it]"))?
-                        }
-                    },
-                    None => Err(format!("{string} need a language, try [en, fr, it]"))?
-                }
-            }
-            _ => Err(format!("{string} is an undefined flag"))?
-        })
-    }
+/* ---------- COMMAND LINE ---------- */
+#[derive(Parser)]
+#[command(name = "expert-system", version, about = "Backward chaining inference engine for propositional rules")]
+#[command(subcommand_required = true, arg_required_else_help = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Language of messages and keywords, any catalog present
+    #[arg(short, long, alias = "langage", global = true, value_name = "LANG", value_parser = Lang::from_code)]
+    language: Option<Lang>,
+
+    /// When to color the output, auto honors NO_COLOR and colors terminals only
+    #[arg(long, global = true, value_enum, value_name = "WHEN")]
+    color: Option<ColorMode>,
+
+    /// Write values as the words true, false and unknown instead of symbols
+    #[arg(long, global = true, overrides_with = "no_plain")]
+    plain: bool,
+
+    /// Write values as symbols, even when the config file turns plain on
+    #[arg(long, global = true, overrides_with = "plain")]
+    no_plain: bool,
 }
 
-pub fn print_helper(error: &str) -> Result<(), String> {
-    println!("cargo run --release -- [maps] [flags]");
-    println!("-h, --help                     print this helper");
-    println!("-i, --interactive              launch interactive mode");
-    println!("-t, --trace                    display algorithm's trace");
-    println!("-v, --variables                display variables before running algorithm (no interactive)");
-    println!("-l, --langage  [fr, en, it]    change default language to chosen one");
-    Err(String::from(error))
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluate the queries of rule files (default when no command is given)
+    Run(RunArgs),
+    /// Open the interactive prompt on rule files
+    Repl(ReplArgs),
+    /// Parse rule files and report their errors without evaluating them
+    Check {
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+    },
+    /// Reformat rule files in place
+    Fmt {
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+        /// Only list the files that are not formatted, exit with 1 if any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Run the expectations of every rule file found in directories
+    Test {
+        #[arg(value_name = "DIRECTORIES")]
+        paths: Vec<String>,
+    },
+    /// Evaluate a csv of facts, one case per row
+    Batch {
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+        /// Csv of facts to evaluate, one case per row
+        #[arg(short, long, required = true, value_name = "CASES.CSV")]
+        facts: String,
+        /// Write results in a file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+        /// Re-run the cases each time a file is modified
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Write the rules in another format
+    Export {
+        #[arg(value_enum)]
+        format: Format,
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+        /// Write in a file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+    },
+    /// Write the rule dependency graph as DOT, same as `export dot`
+    Graph {
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+        /// Write in a file instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+    },
+    /// Answer JSON-RPC requests on a unix socket or a localhost port, one session per connection
+    Serve {
+        #[arg(value_name = "MAPS")]
+        files: Vec<String>,
+        /// Listen on a unix socket instead of a port
+        #[arg(long, value_name = "PATH", conflicts_with = "port")]
+        socket: Option<String>,
+        /// Port listened on 127.0.0.1
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+    },
+    /// Speak the language server protocol on stdin and stdout, for editors
+    Lsp,
+    /// Print the shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
 }
 
-pub fn leaks() -> Result<(Vec<String>, Vec<Flag>), String> {
-    let mut files: Vec<String> = Vec::default();
-    let mut flags: Vec<Flag> = Vec::default();
-    let args = args().collect::<Vec<String>>();
-    let mut args_iter = args.iter();
-    args_iter.next();
-    loop {
-        match args_iter.next() {
-            Some(argument) => {
-                match string_to_char(&argument) {
-                    '-' => {
-                        match Flag::from_string(&argument, &mut args_iter)? {
-                            Flag::Help => print_helper("Help asked")?,
-                            f => flags.push(f),
-                        }
-                    },
-                    _ => files.push(String::from(argument)),
-                }
+#[derive(Args)]
+struct RunArgs {
+    #[arg(value_name = "MAPS")]
+    files: Vec<String>,
+    /// Display algorithm's trace
+    #[arg(short, long, overrides_with = "no_trace")]
+    trace: bool,
+    /// Do not display the trace, even when the config file turns it on
+    #[arg(long, overrides_with = "trace")]
+    no_trace: bool,
+    /// Display variables before running algorithm
+    #[arg(short, long)]
+    variables: bool,
+    /// Re-run the queries and print what changed each time a file is modified
+    #[arg(short, long)]
+    watch: bool,
+    /// Exit with an error if a query differs from its expected value
+    #[arg(short, long, value_name = "X=true,...", value_parser = parse_expectations)]
+    expect: Option<Vec<(String, Answer)>>,
+    /// Set variables or aliases to true on top of the files' facts, `\,` is a comma of an alias
+    #[arg(long, value_name = "X,Y,...", value_parser = parse_names)]
+    set: Vec<Vec<String>>,
+    /// Set variables or aliases back to false, overriding the files' facts
+    #[arg(long, value_name = "X,Y,...", value_parser = parse_names)]
+    unset: Vec<Vec<String>>,
+    /// Query variables or aliases on top of the files' queries
+    #[arg(long, value_name = "X,Y,...", value_parser = parse_names)]
+    query: Vec<Vec<String>>,
+    // former flags of the interactive mode, `repl` replaces them
+    #[arg(short, long, hide = true)]
+    interactive: bool,
+    #[arg(short, long, hide = true)]
+    script: Option<String>,
+    #[arg(long, hide = true)]
+    stop_on_error: bool,
+}
 
+#[derive(Args)]
+struct ReplArgs {
+    #[arg(value_name = "MAPS")]
+    files: Vec<String>,
+    /// Display algorithm's trace
+    #[arg(short, long, overrides_with = "no_trace")]
+    trace: bool,
+    /// Do not display the trace, even when the config file turns it on
+    #[arg(long, overrides_with = "trace")]
+    no_trace: bool,
+    /// Run the commands of a file instead of prompting, - for stdin
+    #[arg(short, long, value_name = "FILE.ESC")]
+    script: Option<String>,
+    /// Stop a script at its first failing command
+    #[arg(long)]
+    stop_on_error: bool,
+    /// Text shown before each command
+    #[arg(long)]
+    prompt: Option<String>,
+}
+
+fn parse_expectations(expectations: &str) -> Result<Vec<(String, Answer)>, Error> {
+    let mut expected = Vec::new();
+    for expectation in expectations.split(',') {
+        match expectation.rsplit_once('=') {
+            Some((name, value)) => match Answer::from_str(value) {
+                Some(answer) => expected.push((String::from(name.trim()), answer)),
+                None => Err(Error::InvalidAnswer(value.to_string()))?
             },
+            None => Err(Error::InvalidExpectation(expectation.to_string()))?
+        }
+    }
+    Ok(expected)
+}
+
+// Names separated by commas, an alias keeps its own commas written `\,`
+fn parse_names(names: &str) -> Result<Vec<String>, Error> {
+    let mut parsed = vec![String::new()];
+    let mut chars = names.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(',')) => parsed.last_mut().unwrap().push(chars.next().unwrap()),
+            (',', _) => parsed.push(String::new()),
+            (c, _) => parsed.last_mut().unwrap().push(c)
+        }
+    }
+    Ok(parsed.into_iter().map(|name| String::from(name.trim())).filter(|name| !name.is_empty()).collect())
+}
+
+/* ---------- ALIASES ---------- */
+// Index of the first argument after the global options, `-l fr`, `--color=never` or `--plain`
+fn after_globals(args: &[String], commands: &clap::Command) -> usize {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let (name, inline) = match (arg.strip_prefix("--"), arg.strip_prefix('-')) {
+            (Some(long), _) => (long.split('=').next().unwrap_or_default(), long.contains('=')),
+            (None, Some(short)) if !short.is_empty() => (&short[..short.chars().next().map(char::len_utf8).unwrap_or(0)], short.chars().count() > 1),
+            _ => break
+        };
+        let global = commands.get_arguments().filter(|a| a.is_global_set()).find(|a| match arg.starts_with("--") {
+            true => a.get_long() == Some(name) || a.get_all_aliases().unwrap_or_default().contains(&name),
+            false => a.get_short().map(String::from).as_deref() == Some(name)
+        });
+        match global {
+            Some(global) => i += 1 + usize::from(global.get_action().takes_values() && !inline),
             None => break
         }
     }
-    if files.len() == 0 && !flags.iter().any(|f| f == &Flag::Interactive) {
-        print_helper("No map given")?
+    i
+}
+
+// Without a command the arguments are the ones of `run`, as before subcommands existed, piped rules are run too.
+// Global options may come first, the command is looked for after them
+fn with_command(mut args: Vec<String>) -> Vec<String> {
+    let commands = Cli::command();
+    let i = after_globals(&args, &commands).min(args.len());
+    match args.get(i) {
+        Some(first) if ["-h", "--help", "-V", "--version"].contains(&first.as_str()) => (),
+        Some(first) if commands.find_subcommand(first).is_some() || first == "help" => (),
+        Some(_) => args.insert(i, String::from("run")),
+        None if !stdin().is_terminal() => args.push(String::from("run")),
+        None => ()
+    }
+    args
+}
+
+// Flags of the switches that are on
+fn switches(switches: Vec<(Flag, bool)>) -> Vec<Flag> {
+    switches.into_iter().filter(|(_, on)| *on).map(|(flag, _)| flag).collect()
+}
+
+impl Command {
+    fn into_flags(self) -> (Vec<String>, Vec<Flag>) {
+        match self {
+            Command::Run(run) => {
+                let mut flags = switches(vec![
+                    (Flag::Trace, run.trace),
+                    (Flag::NoTrace, run.no_trace),
+                    (Flag::Variables, run.variables),
+                    (Flag::Watch, run.watch),
+                    (Flag::Interactive, run.interactive),
+                    (Flag::StopOnError, run.stop_on_error)
+                ]);
+                flags.extend(run.expect.map(Flag::Expect));
+                flags.extend([Flag::Set(run.set.concat()), Flag::Unset(run.unset.concat()), Flag::Query(run.query.concat())]);
+                flags.extend(run.script.map(Flag::Script));
+                (run.files, flags)
+            },
+            Command::Repl(repl) => {
+                let mut flags = switches(vec![
+                    (Flag::Interactive, true),
+                    (Flag::Trace, repl.trace),
+                    (Flag::NoTrace, repl.no_trace),
+                    (Flag::StopOnError, repl.stop_on_error)
+                ]);
+                flags.extend(repl.script.map(Flag::Script));
+                flags.extend(repl.prompt.map(Flag::Prompt));
+                (repl.files, flags)
+            },
+            Command::Check { files } => (files, vec![Flag::Check]),
+            Command::Fmt { files, check } => (files, vec![Flag::Fmt { check }]),
+            Command::Test { paths } => (paths, vec![Flag::Test]),
+            Command::Batch { files, facts, output, watch } => {
+                let mut flags = switches(vec![(Flag::Batch, true), (Flag::Watch, watch)]);
+                flags.push(Flag::Facts(facts));
+                flags.extend(output.map(Flag::Output));
+                (files, flags)
+            },
+            Command::Export { format, files, output } => (files, [Flag::Export(format)].into_iter().chain(output.map(Flag::Output)).collect()),
+            Command::Graph { files, output } => (files, [Flag::Export(Format::Dot)].into_iter().chain(output.map(Flag::Output)).collect()),
+            Command::Serve { files, socket, port } => (files, vec![Flag::Serve(socket.map(Address::Unix).unwrap_or(Address::Tcp(port)))]),
+            Command::Lsp => (Vec::new(), vec![Flag::Lsp]),
+            Command::Completions { shell } => (Vec::new(), vec![Flag::Completions(shell)])
+        }
+    }
+}
+
+pub fn print_completions(shell: Shell) {
+    generate(shell, &mut Cli::command(), "expert-system", &mut stdout());
+}
+
+fn usage(e: clap::Error) -> ! {
+    let _ = e.print();
+    exit(match e.use_stderr() {
+        true => ExitStatus::Usage as i32,
+        false => ExitStatus::AllTrue as i32
+    })
+}
+
+// Help and version are printed here, a bad command line exits with the usage status.
+// A command reading maps without any reads them from stdin when it is piped
+pub fn leaks() -> (Vec<String>, Vec<Flag>) {
+    // errors of the command line are written in the language of the environment
+    if let Some(lang) = Lang::from_env() {
+        lang.set_current();
+    }
+    let cli = Cli::try_parse_from(with_command(args().collect())).unwrap_or_else(|e| usage(e));
+    let (mut files, mut flags) = cli.command.into_flags();
+    let needs_maps = !flags.iter().any(|flag| matches!(flag, Flag::Test | Flag::Interactive | Flag::Script(_) | Flag::Completions(_) | Flag::Serve(_) | Flag::Lsp));
+    if files.is_empty() && needs_maps {
+        match stdin().is_terminal() {
+            true => usage(Cli::command().error(ErrorKind::MissingRequiredArgument, "no map given, pass files or pipe one with -")),
+            false => files.push(String::from("-"))
+        }
+    }
+    flags.extend(cli.language.map(Flag::Lang));
+    flags.extend(cli.color.map(Flag::Color));
+    flags.extend(switches(vec![(Flag::Plain, cli.plain), (Flag::NoPlain, cli.no_plain)]));
+    (files, flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keep_escaped_commas() {
+        assert_eq!(parse_names("A, It rains\\, a lot ,B,").unwrap(), vec!["A", "It rains, a lot", "B"]);
+        assert_eq!(parse_names("a\\b").unwrap(), vec!["a\\b"]);
+    }
+
+    fn arguments(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn commands_come_after_global_options() {
+        assert_eq!(with_command(arguments("es -l fr test test_files")), arguments("es -l fr test test_files"));
+        assert_eq!(with_command(arguments("es --langage=fr --plain rules.ru")), arguments("es --langage=fr --plain run rules.ru"));
+        assert_eq!(with_command(arguments("es -lfr --color never rules.ru -t")), arguments("es -lfr --color never run rules.ru -t"));
+        assert_eq!(with_command(arguments("es --trace rules.ru")), arguments("es run --trace rules.ru"));
+        assert_eq!(with_command(arguments("es --plain --help")), arguments("es --plain --help"));
     }
-    Ok((files, flags))
 }
@@ -0,0 +1,21 @@
+// The engine, used by the command line and embedded in C programs through `ffi`.
+// Its parsers return options since before it was a library, they are kept as they are
+#![allow(clippy::should_implement_trait)]
+
+pub mod algo;
+pub mod models;
+pub mod parsing;
+pub mod leakser;
+pub mod utils;
+pub mod interactive;
+pub mod translate;
+pub mod tester;
+pub mod batch;
+pub mod export;
+mod prompt;
+pub mod watch;
+pub mod config;
+pub mod format;
+pub mod serve;
+pub mod lsp;
+pub mod ffi;
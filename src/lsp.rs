@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::io::{stdin, stdout, BufRead, Write};
+use colored::control::set_override;
+use serde_json::{json, Value};
+use crate::models::{Variable, ExitStatus};
+use crate::parsing::{source_errors, find_named, english_keyword, is_keyword};
+use crate::translate::{Error, Lang};
+
+// Codes of the JSON-RPC 2.0 and language server specifications
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_FAILED: i64 = -32803;
+const SEVERITY_ERROR: i64 = 1;
+const KIND_VARIABLE: i64 = 6;
+const SYNC_FULL: i64 = 1;
+
+/* ---------- DOCUMENTS ---------- */
+struct Document {
+    lines: Vec<String>,
+    variables: HashMap<char, Variable>,
+    lang: Lang
+}
+
+// Parses the whole text at each change, the lines in error are left out of the variables
+fn open_document(text: &str) -> (Document, Vec<(usize, Error)>) {
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    let errors = source_errors(&mut variables, text);
+    let lines: Vec<String> = text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line).to_string()).collect();
+    let lang = lines.iter()
+        .find_map(|line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["lang", code] => Lang::from_code(code).ok(),
+            _ => None
+        })
+        .unwrap_or_else(Lang::current);
+    (Document { lines, variables, lang }, errors)
+}
+
+/* ---------- TOKENS ---------- */
+// A name in a line, the bytes of a quoted one exclude its quotes
+struct Token {
+    start: usize,
+    end: usize,
+    text: String,
+    quoted: bool
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || "\"()!+^&|=<>?{}\\".contains(c)
+}
+
+// Comments start at the first `#`, as the parser reads them
+fn code(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default()
+}
+
+fn tokens(code: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = code.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            let end = chars.by_ref().find(|(_, c)| *c == '"').map(|(j, _)| j).unwrap_or(code.len());
+            tokens.push(Token { start: i + 1, end, text: code[i + 1..end].to_string(), quoted: true });
+        } else if !is_separator(c) {
+            let mut end = i + c.len_utf8();
+            while let Some((j, next)) = chars.next_if(|(_, next)| !is_separator(*next)) {
+                end = j + next.len_utf8();
+            }
+            tokens.push(Token { start: i, end, text: code[i..end].to_string(), quoted: false });
+        }
+    }
+    tokens
+}
+
+// Tokens that may name variables, scenario and language names are left out
+fn named_tokens(line: &str) -> Vec<Token> {
+    let tokens = tokens(code(line));
+    let skipped = matches!(tokens.first().map(|t| t.text.as_str()), Some("scenario" | "lang"));
+    tokens.into_iter().enumerate().filter(|(i, _)| !(skipped && *i == 1)).map(|(_, token)| token).collect()
+}
+
+fn is_alias(document: &Document, text: &str) -> bool {
+    document.variables.values().any(|v| v.alias_true.as_deref() == Some(text) || v.alias_false.as_deref() == Some(text))
+}
+
+// A bare word of variable letters, as `AB` in `=AB`
+fn is_letters(document: &Document, token: &Token) -> bool {
+    !token.quoted
+        && !is_keyword(&token.text, &document.lang)
+        && !is_alias(document, &token.text)
+        && token.text.chars().all(|c| document.variables.contains_key(&c))
+}
+
+#[derive(PartialEq)]
+enum Name {
+    Letter(char),
+    Alias(String)
+}
+
+// The name under the cursor with the variable it refers to
+fn name_at(document: &Document, line: usize, byte: usize) -> Option<(char, Name, usize, usize)> {
+    let token = named_tokens(document.lines.get(line)?).into_iter().find(|t| t.start <= byte && byte <= t.end)?;
+    if is_letters(document, &token) {
+        let (i, c) = token.text.char_indices().rfind(|(i, _)| token.start + i <= byte)?;
+        return Some((c, Name::Letter(c), token.start + i, token.start + i + c.len_utf8()))
+    }
+    match find_named(&document.variables, &token.text) {
+        Some((key, _)) if is_alias(document, &token.text) => Some((key, Name::Alias(token.text), token.start, token.end)),
+        _ => None
+    }
+}
+
+// Every place a variable is named, with the bytes of the name in its line
+fn occurrences(document: &Document, name: &Name) -> Vec<(usize, usize, usize)> {
+    let mut found = Vec::new();
+    for (number, line) in document.lines.iter().enumerate() {
+        for token in named_tokens(line) {
+            match name {
+                Name::Alias(alias) if token.text == *alias => found.push((number, token.start, token.end)),
+                Name::Letter(letter) if is_letters(document, &token) => {
+                    found.extend(token.text.char_indices()
+                        .filter(|(_, c)| c == letter)
+                        .map(|(i, c)| (number, token.start + i, token.start + i + c.len_utf8())));
+                },
+                _ => ()
+            }
+        }
+    }
+    found
+}
+
+// The `def` line of a variable, with the bytes of its letter
+fn definition(document: &Document, key: char) -> Option<(usize, usize, usize)> {
+    document.lines.iter().enumerate().find_map(|(number, line)| {
+        let tokens = tokens(code(line));
+        let first = tokens.first()?;
+        let is_def = first.text == "def" || english_keyword(&first.text, &document.lang) == Some("def");
+        match tokens.get(1) {
+            Some(letter) if is_def && letter.text == key.to_string() => Some((number, letter.start, letter.end)),
+            _ => None
+        }
+    })
+}
+
+/* ---------- POSITIONS ---------- */
+// Positions count UTF-16 code units, as the protocol does by default
+fn byte_offset(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+fn range(document: &Document, line: usize, start: usize, end: usize) -> Value {
+    let text = document.lines.get(line).map(|l| l.as_str()).unwrap_or_default();
+    json!({
+        "start": { "line": line, "character": text[..start].encode_utf16().count() },
+        "end": { "line": line, "character": text[..end].encode_utf16().count() }
+    })
+}
+
+// The document, line and byte a request points at
+fn cursor<'a>(documents: &'a HashMap<String, Document>, params: &Value) -> Option<(&'a str, &'a Document, usize, usize)> {
+    let uri = params.pointer("/textDocument/uri")?.as_str()?;
+    let (uri, document) = documents.get_key_value(uri)?;
+    let line = params.pointer("/position/line")?.as_u64()? as usize;
+    let character = params.pointer("/position/character")?.as_u64()? as usize;
+    let byte = byte_offset(document.lines.get(line)?, character);
+    Some((uri, document, line, byte))
+}
+
+/* ---------- FEATURES ---------- */
+fn diagnostics(uri: &str, document: &Document, errors: &[(usize, Error)]) -> Value {
+    let diagnostics: Vec<Value> = errors.iter().map(|(line, message)| json!({
+        "range": range(document, *line, 0, document.lines.get(*line).map(|l| l.len()).unwrap_or_default()),
+        "severity": SEVERITY_ERROR,
+        "source": "expert-system",
+        "message": message.to_string()
+    })).collect();
+    json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } })
+}
+
+// The aliases of the variable as its `def` line, then the rules concluding it
+fn hover(documents: &HashMap<String, Document>, params: &Value) -> Value {
+    let (key, line, start, end, document) = match cursor(documents, params).and_then(|(_, document, line, byte)| {
+        name_at(document, line, byte).map(|(key, _, start, end)| (key, line, start, end, document))
+    }) {
+        Some(found) => found,
+        None => return Value::Null
+    };
+    let variable = &document.variables[&key];
+    let aliases: Vec<String> = [&variable.alias_true, &variable.alias_false].into_iter().flatten().map(|alias| format!("\"{alias}\"")).collect();
+    let mut lines = vec![match aliases.is_empty() {
+        true => key.to_string(),
+        false => format!("def {key} {}", aliases.join(" "))
+    }];
+    lines.extend(variable.rules.iter().map(|rule| rule.to_string().trim().to_string()));
+    json!({
+        "contents": { "kind": "markdown", "value": format!("```\n{}\n```", lines.join("\n")) },
+        "range": range(document, line, start, end)
+    })
+}
+
+fn goto_definition(documents: &HashMap<String, Document>, params: &Value) -> Value {
+    cursor(documents, params)
+        .and_then(|(uri, document, line, byte)| {
+            let (key, _, _, _) = name_at(document, line, byte)?;
+            let (line, start, end) = definition(document, key)?;
+            Some(json!({ "uri": uri, "range": range(document, line, start, end) }))
+        })
+        .unwrap_or(Value::Null)
+}
+
+// Aliases are only proposed inside quotes, letters need no completion
+fn completion(documents: &HashMap<String, Document>, params: &Value) -> Value {
+    let (document, line, byte) = match cursor(documents, params) {
+        Some((_, document, line, byte)) => (document, line, byte),
+        None => return json!([])
+    };
+    let before = &document.lines[line][..byte];
+    if before.contains('#') || before.matches('"').count() % 2 == 0 {
+        return json!([])
+    }
+    let mut keys: Vec<&char> = document.variables.keys().collect();
+    keys.sort();
+    let items: Vec<Value> = keys.into_iter().flat_map(|key| {
+        let variable = &document.variables[key];
+        [(&variable.alias_true, key.to_string()), (&variable.alias_false, format!("!{key}"))]
+            .into_iter()
+            .filter_map(|(alias, detail)| alias.as_ref().map(|alias| json!({ "label": alias, "kind": KIND_VARIABLE, "detail": detail })))
+            .collect::<Vec<Value>>()
+    }).collect();
+    json!(items)
+}
+
+// The new name must be free, as a `def` line would require
+fn check_new_name(document: &Document, name: &Name, new_name: &str) -> Result<(), Error> {
+    match name {
+        Name::Letter(_) => {
+            let mut chars = new_name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_alphabetic() && document.variables.contains_key(&c) => Err(Error::VariableExists(new_name.to_string()))?,
+                (Some(c), None) if c.is_alphabetic() => (),
+                _ => Err(Error::InvalidVariableName(new_name.to_string()))?
+            }
+        },
+        Name::Alias(_) => {
+            if new_name.contains('"') {
+                Err(Error::UnclosedQuote(new_name.to_string()))?
+            } else if is_keyword(new_name, &document.lang) {
+                Err(Error::ReservedWord(new_name.to_string()))?
+            } else if new_name.chars().count() < 2 {
+                Err(Error::AliasTooShort(new_name.to_string()))?
+            } else if let Some((key, _)) = find_named(&document.variables, new_name) {
+                Err(Error::AlreadyAlias(new_name.to_string(), key.to_string()))?
+            }
+        }
+    }
+    Ok(())
+}
+
+// Edits of every occurrence, a bare alias is quoted when the new one is not a single word
+fn rename(documents: &HashMap<String, Document>, params: &Value) -> Result<Value, (i64, String)> {
+    let (uri, document, line, byte) = cursor(documents, params).ok_or((INVALID_PARAMS, Error::UnknownPosition.to_string()))?;
+    let new_name = params.get("newName").and_then(Value::as_str).ok_or((INVALID_PARAMS, Error::NotString(String::from("newName")).to_string()))?;
+    let (_, name, _, _) = name_at(document, line, byte).ok_or((REQUEST_FAILED, Error::ExpectedVariableName.to_string()))?;
+    check_new_name(document, &name, new_name).map_err(|e| (REQUEST_FAILED, e.to_string()))?;
+    let bare = new_name.chars().any(is_separator);
+    let edits: Vec<Value> = occurrences(document, &name).into_iter().map(|(line, start, end)| {
+        let quoted = document.lines[line][..start].ends_with('"');
+        let text = match bare && !quoted {
+            true => format!("\"{new_name}\""),
+            false => new_name.to_string()
+        };
+        json!({ "range": range(document, line, start, end), "newText": text })
+    }).collect();
+    Ok(json!({ "changes": { uri: edits } }))
+}
+
+/* ---------- SERVER ---------- */
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": SYNC_FULL,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "completionProvider": { "triggerCharacters": ["\""] },
+            "renameProvider": true
+        },
+        "serverInfo": { "name": "expert-system", "version": env!("CARGO_PKG_VERSION") }
+    })
+}
+
+// Messages are a `Content-Length` header and a JSON body, a body that does not parse is read as null
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None
+        }
+        match header.trim().split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("content-length") => length = value.trim().parse::<usize>().ok(),
+            Some(_) => (),
+            None if header.trim().is_empty() => break,
+            None => ()
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    Some(serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> bool {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len()).and_then(|_| writer.flush()).is_ok()
+}
+
+// Answers requests on stdin and stdout until the client asks to exit, the status tells whether it shut down first
+pub fn lsp() -> ExitStatus {
+    // messages are sent as text, without terminal colors
+    set_override(false);
+    let mut documents: HashMap<String, Document> = HashMap::new();
+    let mut shutdown = false;
+    let (mut reader, mut writer) = (stdin().lock(), stdout().lock());
+    while let Some(message) = read_message(&mut reader) {
+        let id = message.get("id").cloned();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let uri = params.pointer("/textDocument/uri").and_then(Value::as_str).map(String::from);
+        let mut notifications = Vec::new();
+        let result: Result<Value, (i64, String)> = match message.get("method").and_then(Value::as_str).unwrap_or_default() {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                shutdown = true;
+                Ok(Value::Null)
+            },
+            "exit" => break,
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = params.pointer("/textDocument/text")
+                    .or_else(|| params.get("contentChanges").and_then(Value::as_array).and_then(|changes| changes.last()?.get("text")))
+                    .and_then(Value::as_str);
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    let (document, errors) = open_document(text);
+                    notifications.push(diagnostics(&uri, &document, &errors));
+                    documents.insert(uri, document);
+                }
+                Ok(Value::Null)
+            },
+            "textDocument/didClose" => {
+                if let Some(document) = uri.as_ref().and_then(|uri| documents.remove(uri)) {
+                    notifications.push(diagnostics(uri.as_deref().unwrap_or_default(), &document, &[]));
+                }
+                Ok(Value::Null)
+            },
+            "textDocument/hover" => Ok(hover(&documents, &params)),
+            "textDocument/definition" => Ok(goto_definition(&documents, &params)),
+            "textDocument/completion" => Ok(completion(&documents, &params)),
+            "textDocument/rename" => rename(&documents, &params),
+            method => Err((METHOD_NOT_FOUND, Error::MethodNotFound(method.to_string()).to_string()))
+        };
+        // notifications get no response, even for an unknown method
+        if let Some(id) = id {
+            notifications.push(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+            });
+        }
+        if !notifications.iter().all(|message| write_message(&mut writer, message)) {
+            break
+        }
+    }
+    match shutdown {
+        true => ExitStatus::AllTrue,
+        false => ExitStatus::Unexpected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(uri: &str, line: usize, character: usize) -> Value {
+        json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        // `é` is two bytes and one unit, `𝔸` four bytes and two units
+        let line = "é𝔸 => B";
+        assert_eq!(byte_offset(line, 0), 0);
+        assert_eq!(byte_offset(line, 1), 2);
+        assert_eq!(byte_offset(line, 3), 6);
+        assert_eq!(byte_offset(line, 100), line.len());
+        let (document, _) = open_document(line);
+        assert_eq!(range(&document, 0, 2, 6), json!({ "start": { "line": 0, "character": 1 }, "end": { "line": 0, "character": 3 } }));
+    }
+
+    #[test]
+    fn names_lead_to_their_definition() {
+        let (document, errors) = open_document("def A \"sun\" \"moon\"\n=A\n\"sun\" => B\n?B\n");
+        assert!(errors.is_empty());
+        let documents = HashMap::from([(String::from("file:///a.ru"), document)]);
+        let expected = json!({ "uri": "file:///a.ru", "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 5 } } });
+        assert_eq!(goto_definition(&documents, &position("file:///a.ru", 2, 2)), expected);
+        assert_eq!(goto_definition(&documents, &position("file:///a.ru", 1, 1)), expected);
+        assert_eq!(goto_definition(&documents, &position("file:///b.ru", 1, 1)), Value::Null);
+    }
+
+    #[test]
+    fn renames_quote_bare_aliases_and_refuse_taken_names() {
+        let (document, _) = open_document("def A \"sun\" \"moon\"\nsun => B\n?B\n");
+        let documents = HashMap::from([(String::from("u"), document)]);
+        let mut params = position("u", 1, 1);
+        params["newName"] = json!("red sun");
+        let edits = rename(&documents, &params).unwrap()["changes"]["u"].clone();
+        assert_eq!(edits[0]["newText"], json!("red sun"));
+        assert_eq!(edits[1]["newText"], json!("\"red sun\""));
+        params["newName"] = json!("moon");
+        assert!(matches!(rename(&documents, &params), Err((REQUEST_FAILED, _))));
+    }
+
+    #[test]
+    fn diagnostics_cover_the_lines_in_error() {
+        let (document, errors) = open_document("A => B\nA => (B\n");
+        let published = diagnostics("u", &document, &errors);
+        let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], json!(1));
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+        let mut written = Vec::new();
+        assert!(write_message(&mut written, &message));
+        assert_eq!(read_message(&mut written.as_slice()), Some(message));
+        assert_eq!(read_message(&mut "Content-Length: 3\r\n\r\n{{{".as_bytes()), Some(Value::Null));
+        assert_eq!(read_message(&mut "".as_bytes()), None);
+    }
+}
@@ -1,37 +1,145 @@
-mod algo;
-mod models;
-mod parsing;
-mod leakser;
-mod utils;
-mod interactive;
-mod translate;
-
 use std::collections::HashMap;
-use parsing::{fill_maps};
-use models::Variable;
-use algo::algo_v1;
-use leakser::{leaks, Flag};
-use utils::print_variables;
-use translate::Lang;
-use interactive::interactive_mode;
+use std::process::exit;
+use std::fs::write;
+use std::io::{stdin, IsTerminal};
+use colored::Colorize;
+use expert_system::parsing::{fill_maps, apply_scenario, set_facts, add_queries};
+use expert_system::models::{Variable, Answer, ExitStatus};
+use expert_system::algo::algo_v1;
+use expert_system::leakser::{leaks, print_completions, Flag};
+use expert_system::utils::{print_variables, set_plain};
+use expert_system::interactive::{interactive_mode, script_mode};
+use expert_system::tester::{test_mode, check_mode, check_expectations};
+use expert_system::batch::{batch_mode, batch_results};
+use expert_system::export::export;
+use expert_system::format::fmt_mode;
+use expert_system::serve::serve;
+use expert_system::lsp::lsp;
+use expert_system::watch::{watch, file_results};
+use expert_system::translate::{Lang, Error};
+use expert_system::config::Config;
+
+fn parse_error(e: Error) -> (Error, ExitStatus) {
+    let status = ExitStatus::from_error(&e);
+    (e, status)
+}
+
+fn answers_status(variables: &HashMap<char, Variable>, answers: &[(char, Answer)], expected: &Option<Vec<(String, Answer)>>) -> ExitStatus {
+    match expected {
+        Some(expected) => {
+            let failures = check_expectations(variables, answers, expected);
+            for failure in failures.iter() {
+                eprintln!("{failure}");
+            }
+            match failures.is_empty() {
+                true => ExitStatus::AllTrue,
+                false => ExitStatus::Unexpected
+            }
+        },
+        None => ExitStatus::from_answers(answers)
+    }
+}
 
-fn main() -> Result<(), String> {
-    let (files, mut flags) = leaks()?;
-    let lang = match flags.iter().find(|flag| match flag {Flag::Lang(_) => true, _ => false}).unwrap_or(&Flag::Lang(Lang::En)) {
-        Flag::Lang(l) => l,
-        _ => unreachable!()
-    };
-    if flags.iter().any(|f| f == &Flag::Interactive) {
+// Facts and queries of the command line, applied after the file's ones
+fn apply_overrides(variables: &mut HashMap<char, Variable>, flags: &[Flag]) -> Result<(), Error> {
+    for flag in flags {
+        match flag {
+            Flag::Set(names) => set_facts(variables, names, true)?,
+            Flag::Unset(names) => set_facts(variables, names, false)?,
+            Flag::Query(names) => add_queries(variables, names)?,
+            _ => ()
+        }
+    }
+    Ok(())
+}
+
+fn run() -> Result<ExitStatus, (Error, ExitStatus)> {
+    let (files, mut flags) = leaks();
+    Config::load().map_err(|e| (e, ExitStatus::Usage))?.apply(&mut flags);
+    if let Some(color) = flags.iter().find_map(|flag| match flag {Flag::Color(c) => Some(c), _ => None}) {
+        color.apply();
+    }
+    set_plain(flags.contains(&Flag::Plain));
+    let lang = &flags.iter().find_map(|flag| match flag {Flag::Lang(l) => Some(l.clone()), _ => None}).unwrap_or_default();
+    lang.set_current();
+    let expected = flags.iter().find_map(|flag| match flag {Flag::Expect(e) => Some(e.clone()), _ => None});
+    let mut status = ExitStatus::AllTrue;
+    if let Some(shell) = flags.iter().find_map(|flag| match flag {Flag::Completions(s) => Some(*s), _ => None}) {
+        print_completions(shell);
+    } else if let Some(address) = flags.iter().find_map(|flag| match flag {Flag::Serve(a) => Some(a), _ => None}) {
+        serve(&files, &flags, address).map_err(|e| match e {
+            Error::Io(..) => (e, ExitStatus::Usage),
+            e => parse_error(e)
+        })?;
+    } else if flags.contains(&Flag::Lsp) {
+        status = lsp();
+    } else if flags.contains(&Flag::Test) {
+        let paths = match files.is_empty() {
+            true => vec![String::from(".")],
+            false => files.clone()
+        };
+        status = test_mode(&paths).map_err(|e| (e, ExitStatus::Usage))?;
+    } else if flags.contains(&Flag::Batch) {
+        let facts = flags.iter().find_map(|flag| match flag {Flag::Facts(f) => Some(f), _ => None}).unwrap();
+        let output = flags.iter().find_map(|flag| match flag {Flag::Output(o) => Some(o), _ => None});
+        if flags.contains(&Flag::Watch) {
+            let watched: Vec<String> = files.iter().cloned().chain([facts.clone()]).collect();
+            watch(&watched, || batch_results(&files, facts, output));
+        }
+        status = batch_mode(&files, facts, output).map_err(parse_error)?;
+    } else if flags.contains(&Flag::Check) {
+        status = check_mode(&files);
+    } else if let Some(check) = flags.iter().find_map(|flag| match flag {Flag::Fmt { check } => Some(*check), _ => None}) {
+        status = fmt_mode(&files, check);
+    } else if let Some(format) = flags.iter().find_map(|flag| match flag {Flag::Export(f) => Some(*f), _ => None}) {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        for file in files.iter() {
+            fill_maps(&mut variables, file, true).map_err(parse_error)?;
+        }
+        match flags.iter().find_map(|flag| match flag {Flag::Output(o) => Some(o), _ => None}) {
+            Some(path) => write(path, export(&variables, format).map_err(parse_error)?).map_err(|e| (Error::Io(path.clone(), e.to_string()), ExitStatus::Usage))?,
+            None => print!("{}", export(&variables, format).map_err(parse_error)?)
+        }
+    } else if let Some(script) = flags.iter().find_map(|flag| match flag {Flag::Script(s) => Some(s), _ => None}) {
+        status = script_mode(&files, &flags, script).map_err(|e| (e, ExitStatus::Usage))?;
+    } else if flags.iter().any(|f| f == &Flag::Interactive) && !stdin().is_terminal() {
+        status = script_mode(&files, &flags, "-").map_err(|e| (e, ExitStatus::Usage))?;
+    } else if flags.iter().any(|f| f == &Flag::Interactive) {
         interactive_mode(&files, &mut flags);
+    } else if flags.contains(&Flag::Watch) {
+        watch(&files, || file_results(&files, |variables| apply_overrides(variables, &flags)));
     } else {
         for file in files.iter() {
             let mut variables: HashMap<char, Variable> = HashMap::new();
-            fill_maps(&mut variables, file, true)?;
+            let scenarios = fill_maps(&mut variables, file, true).map_err(parse_error)?;
+            apply_overrides(&mut variables, &flags).map_err(parse_error)?;
             if flags.contains(&Flag::Variables) {
                 print_variables(&variables);
             }
-            algo_v1(&mut variables, flags.contains(&Flag::Trace), &lang);
+            if scenarios.is_empty() || variables.values().any(|v| v.requested) {
+                let mut top_level = variables.clone();
+                let answers = algo_v1(&mut top_level, flags.contains(&Flag::Trace), lang);
+                status = status.max(answers_status(&top_level, &answers, &expected));
+            }
+            for scenario in scenarios.iter() {
+                println!("{}", format!("[{}]", scenario.name).blue().bold());
+                let mut scenario_variables = variables.clone();
+                apply_scenario(&mut scenario_variables, scenario, true).map_err(parse_error)?;
+                apply_overrides(&mut scenario_variables, &flags).map_err(parse_error)?;
+                let answers = algo_v1(&mut scenario_variables, flags.contains(&Flag::Trace), lang);
+                status = status.max(answers_status(&scenario_variables, &answers, &expected));
+            }
         }
     }
-    Ok(())
-}
\ No newline at end of file
+    Ok(status)
+}
+
+fn main() {
+    match run() {
+        Ok(status) => exit(status as i32),
+        Err((e, status)) => {
+            eprintln!("{}: {e}", Lang::current().message("error"));
+            exit(status as i32)
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use std::fmt;
 use crate::utils::tick_or_cross;
 use std::collections::HashMap;
+use crate::translate::Error;
+use serde::{Serialize, Deserialize};
 
 /* ---------- RULE ---------- */
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Rule {
     pub input: BTree,
     pub output: BTree,
@@ -17,7 +19,7 @@ impl fmt::Display for Rule {
 }
 
 /* ---------- VARIABLE ---------- */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variable {
     pub value: bool,
     pub locked: bool,
@@ -42,7 +44,7 @@ impl fmt::Display for Variable {
 }
 
 impl Variable {
-    pub fn default() -> Self {
+    pub(crate) fn default() -> Self {
         Variable {
             value: false,
             locked: false,
@@ -53,7 +55,7 @@ impl Variable {
         }
     }
 
-    pub fn request() -> Self {
+    pub(crate) fn request() -> Self {
         Variable {
             value: false,
             locked: false,
@@ -64,7 +66,7 @@ impl Variable {
         }
     }
 
-    pub fn insert() -> Self {
+    pub(crate) fn insert() -> Self {
         Variable {
             value: true,
             locked: true,
@@ -76,8 +78,85 @@ impl Variable {
     }
 }
 
-/* ---------- OPERATOR ---------- */ 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/* ---------- SCENARIO ---------- */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scenario {
+    pub name: String,
+    pub lines: Vec<String>
+}
+
+/* ---------- ANSWER ---------- */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    True,
+    False,
+    Undetermined
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Answer::True => write!(f, "true"),
+            Answer::False => write!(f, "false"),
+            Answer::Undetermined => write!(f, "undetermined")
+        }
+    }
+}
+
+impl Answer {
+    pub(crate) fn from_str(string: &str) -> Option<Answer> {
+        match string.to_lowercase().trim() {
+            "true" | "1" => Some(Answer::True),
+            "false" | "0" => Some(Answer::False),
+            "undetermined" | "?" => Some(Answer::Undetermined),
+            _ => None
+        }
+    }
+
+    pub(crate) fn negate(&self) -> Answer {
+        match self {
+            Answer::True => Answer::False,
+            Answer::False => Answer::True,
+            Answer::Undetermined => Answer::Undetermined
+        }
+    }
+}
+
+/* ---------- EXIT STATUS ---------- */
+// Ordered by gravity: when several queries or files are evaluated the worst one wins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitStatus {
+    AllTrue = 0,
+    SomeFalse = 1,
+    Undetermined = 2,
+    Inconsistent = 3,
+    ParseError = 4,
+    Unexpected = 5,
+    Usage = 64
+}
+
+impl ExitStatus {
+    // The status of a file that cannot be loaded
+    pub fn from_error(error: &Error) -> ExitStatus {
+        match error.is_contradiction() {
+            true => ExitStatus::Inconsistent,
+            false => ExitStatus::ParseError
+        }
+    }
+
+    pub fn from_answers(answers: &[(char, Answer)]) -> ExitStatus {
+        answers.iter().fold(ExitStatus::AllTrue, |acc, (_, answer)| {
+            acc.max(match answer {
+                Answer::True => ExitStatus::AllTrue,
+                Answer::False => ExitStatus::SomeFalse,
+                Answer::Undetermined => ExitStatus::Undetermined
+            })
+        })
+    }
+}
+
+/* ---------- OPERATOR ---------- */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Operator {
     And,
     Or,
@@ -113,7 +192,7 @@ impl fmt::Display for Operator {
 }
 
 impl Operator {
-    pub fn from_str(string: &str) -> Option<Operator> {
+    pub(crate) fn from_str(string: &str) -> Option<Operator> {
         match string {
             "and" | "&" | "+" => Some(Operator::And),
             "or" | "|" => Some(Operator::Or),
@@ -129,7 +208,7 @@ impl Operator {
         }
     }
 
-    pub fn to_reverse_polish_notation(input: &Vec<Operator>) -> Result<Vec<Operator>, String> {
+    pub(crate) fn to_reverse_polish_notation(input: &Vec<Operator>) -> Result<Vec<Operator>, Error> {
         let mut output: Vec<Operator> = Vec::new();
         let mut stack: Vec<Operator> = Vec::new();
         for operator in input.iter() {
@@ -140,20 +219,20 @@ impl Operator {
                         match stack.pop() {
                             Some(Operator::Parentesis(true)) => break,
                             Some(ope) => output.push(ope),
-                            None => Err("unexpected closing delimiter: ')'")?
+                            None => Err(Error::UnexpectedClosing)?
                         }
                     }
                 },
                 Operator::Not | Operator::Parentesis(true) => stack.push(operator.clone()),
                 _ => {
                     if output.len() == 0 {
-                        Err(format!("unexpected operator {operator}"))?
+                        Err(Error::UnexpectedOperator(operator.to_string()))?
                     }
                     match operator {
                         Operator::IfAndOnlyIf | Operator::Then => {
                             loop {
                                 match stack.pop() {
-                                    Some(Operator::Parentesis(true)) => Err("unclosed delimiter '('")?,
+                                    Some(Operator::Parentesis(true)) => Err(Error::UnclosedDelimiter)?,
                                     Some(ope) => output.push(ope),
                                     None => break
                                 }
@@ -167,7 +246,7 @@ impl Operator {
         }
         loop {
             match stack.pop() {
-                Some(Operator::Parentesis(true)) => Err("unclosed delimiter '('")?,
+                Some(Operator::Parentesis(true)) => Err(Error::UnclosedDelimiter)?,
                 Some(ope) => output.push(ope),
                 None => break
             }
@@ -178,7 +257,7 @@ impl Operator {
 
 /* ---------- BTREE ---------- */
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BTree {
     pub c1: Option<Box<BTree>>,
     pub c2: Option<Box<BTree>>,
@@ -200,11 +279,11 @@ impl BTree {
         }
     }
 
-    pub fn insert_a(&mut self, sub_tree: BTree) {
+    pub(crate) fn insert_a(&mut self, sub_tree: BTree) {
         self.c1 = Some(Box::new(sub_tree));
     }
 
-    pub fn insert_b(&mut self, sub_tree: BTree) {
+    pub(crate) fn insert_b(&mut self, sub_tree: BTree) {
         self.c2 = Some(Box::new(sub_tree));
     }
 
@@ -223,12 +302,12 @@ impl BTree {
         }
     }
 
-    pub fn from_vec(formula: &mut Vec<Operator>) -> Result<BTree, String> {
+    pub(crate) fn from_vec(formula: &mut Vec<Operator>) -> Result<BTree, Error> {
         if let Some(last_op) = formula.pop() {
             let mut ret = match last_op {
                 Operator::B(boole) => return Ok(BTree::new(Operator::B(boole))),
                 Operator::Var(v) => return Ok(BTree::new(Operator::Var(v))),
-                Operator::Parentesis(_) | Operator::IfAndOnlyIf | Operator::Then => Err("unexpected operator {last_op} in btree")?,
+                Operator::Parentesis(_) | Operator::IfAndOnlyIf | Operator::Then => Err(Error::UnexpectedOperator(last_op.to_string()))?,
                 op => BTree::new(op)
             };
             ret.insert_b(BTree::from_vec(formula)?);
@@ -237,10 +316,10 @@ impl BTree {
             }
             return Ok(ret)
         }
-        Err(String::from("Error while parsing formula"))
+        Err(Error::ParsingFormula)
     }
 
-    pub fn eval(&self) -> bool {
+    pub(crate) fn eval(&self) -> bool {
         match calc_formula(&Box::new(self.clone())) {
             Ok(res) => res,
             Err(e) => {
@@ -250,7 +329,7 @@ impl BTree {
         }
     }
 
-    pub fn find_nodes(&self, serch_fn: fn (&Operator) -> bool) -> Vec<Operator> {
+    pub(crate) fn find_nodes(&self, serch_fn: fn (&Operator) -> bool) -> Vec<Operator> {
         let mut ret: Vec<Operator> = Vec::new();
 
         if serch_fn(&self.node) {
@@ -266,7 +345,7 @@ impl BTree {
         ret
     }
 
-    pub fn enrich_bool(&self, variables: &HashMap<char, bool>) -> BTree {
+    pub(crate) fn enrich_bool(&self, variables: &HashMap<char, bool>) -> BTree {
         match &self.node {
             Operator::Var(c) => {
                 if let Some(var) = variables.get(c) {
@@ -289,7 +368,7 @@ impl BTree {
         }
     }
 
-    pub fn enrich(&self, variables: &HashMap<char, Variable>) -> BTree {
+    pub(crate) fn enrich(&self, variables: &HashMap<char, Variable>) -> BTree {
         match &self.node {
             Operator::Var(c) => {
                 if let Some(var) = variables.get(c) {
@@ -313,7 +392,7 @@ impl BTree {
     }
 }
 
-fn calc_formula(tree: &Box<BTree>) -> Result<bool, String> {
+fn calc_formula(tree: &Box<BTree>) -> Result<bool, Error> {
     match (&tree.node, &tree.c1, &tree.c2) {
         (Operator::And, Some(c1), Some(c2)) => Ok(calc_formula(&c1)? & calc_formula(&c2)?),
         (Operator::Or, Some(c1), Some(c2)) => Ok(calc_formula(&c1)? | calc_formula(&c2)?),
@@ -324,7 +403,26 @@ fn calc_formula(tree: &Box<BTree>) -> Result<bool, String> {
         (Operator::Not, None, Some(c2)) => Ok(!calc_formula(&c2)?),
         (Operator::B(b), None, None) => Ok(*b),
         _ => {
-            return Err(String::from("Error while calculating formula"))
+            return Err(Error::CalculatingFormula)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_give_their_worst_status() {
+        assert_eq!(ExitStatus::from_answers(&[]), ExitStatus::AllTrue);
+        assert_eq!(ExitStatus::from_answers(&[('A', Answer::True), ('B', Answer::False)]), ExitStatus::SomeFalse);
+        assert_eq!(ExitStatus::from_answers(&[('A', Answer::Undetermined), ('B', Answer::False)]), ExitStatus::Undetermined);
+    }
+
+    #[test]
+    fn contradictions_are_inconsistent_even_in_a_file() {
+        let contradiction = Error::Contradiction(String::from("A"));
+        assert_eq!(ExitStatus::from_error(&Error::NoRules), ExitStatus::ParseError);
+        assert_eq!(ExitStatus::from_error(&Error::in_file("rules.ru", contradiction)), ExitStatus::Inconsistent);
+    }
+}
@@ -1,13 +1,13 @@
-use std::fs::File;
-use std::io::prelude::*;
 use std::collections::HashMap;
 use crate::{
-    models::{Operator, Variable, Rule, BTree},
-    utils::string_to_char,
+    models::{Operator, Variable, Rule, BTree, Answer, Scenario},
+    utils::{string_to_char, read_source},
+    translate::{Error, Lang},
+    export::from_json,
 };
 use colored::Colorize;
 
-const RESERVED_WORDS: [&'static str; 22] = [
+const RESERVED_WORDS: [&'static str; 25] = [
     "and",
     "&",
     "+",
@@ -27,6 +27,9 @@ const RESERVED_WORDS: [&'static str; 22] = [
     "<=>",
     "if",
     "def",
+    "expect",
+    "scenario",
+    "lang",
     "?",
     "(",
     ")"
@@ -76,7 +79,7 @@ fn check_loop_2(variables: &HashMap<char, Variable>, mut history: Vec<(char, Vec
     }
 }
 
-fn check_loop(variables: &HashMap<char, Variable>, rule: &Rule) -> Result<(), String>{
+fn check_loop(variables: &HashMap<char, Variable>, rule: &Rule) -> Result<(), Error>{
     let outuput_var = rule.output.find_nodes(|ope| match ope {Operator::Var(_) => true, _ => false});
     for var in outuput_var {
         match var {
@@ -91,7 +94,7 @@ fn check_loop(variables: &HashMap<char, Variable>, rule: &Rule) -> Result<(), St
                                     if z != *input_letter.1 {
                                         if let Some((_, error_vec)) = ret_loop.iter().find(|(l_output_letter, _)| l_output_letter == input_letter.0) {
                                             if let Some(_) = error_vec.iter().find(|loop_map| loop_map.get(&output_letter).is_some()) {
-                                                Err(format!("contradiction in rule{rule}"))?
+                                                Err(Error::Contradiction(rule.to_string()))?
                                             }
                                         }
                                     }
@@ -110,20 +113,68 @@ fn check_loop(variables: &HashMap<char, Variable>, rule: &Rule) -> Result<(), St
 }
 
 /* ---------- STRING TRANSFORMATIONS ---------- */
-fn to_splited_string(file: &str) -> Result<Vec<String>, String> {
-    let mut file = File::open(file).map_err(|e| format!("{e}"))?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).map_err(|e| format!("{e}"))?;
-    Ok(contents.split("\n").fold(Vec::new(), |mut acc, line| {
+// A line continues on the next one when it ends with `\` or leaves a parenthesis open
+pub(crate) fn is_continued(line: &str) -> bool {
+    let (depth, _) = line.chars().fold((0, false), |(depth, scoped), c| {
+        match (c, scoped) {
+            ('"', _) => (depth, !scoped),
+            ('(', false) => (depth + 1, scoped),
+            (')', false) => (depth - 1, scoped),
+            _ => (depth, scoped)
+        }
+    });
+    line.trim_end().ends_with('\\') || depth > 0
+}
+
+fn join_continued(line: &str, next: &str) -> String {
+    let line = line.trim_end();
+    let line = line.strip_suffix('\\').unwrap_or(line).trim_end();
+    format!("{line} {}", next.trim()).trim().to_string()
+}
+
+// Appends a line to the pending one, returns the whole line once it is complete, a blank line always completes it
+pub(crate) fn continue_line(pending: &mut String, line: &str) -> Option<String> {
+    if !pending.is_empty() && line.trim().is_empty() {
+        return Some(join_continued(&std::mem::take(pending), ""))
+    }
+    let joined = join_continued(pending, line);
+    match is_continued(&joined) {
+        true => {
+            *pending = joined;
+            None
+        },
+        false => {
+            pending.clear();
+            Some(joined)
+        }
+    }
+}
+
+// Lines joined with their continuations and without comments, each with the index of the line it starts on
+type NumberedLines = Vec<(usize, String)>;
+
+fn split_lines(contents: &str) -> NumberedLines {
+    let mut pending = String::new();
+    let mut start = 0;
+    contents.split("\n").chain([""]).enumerate().fold(Vec::new(), |mut acc, (number, line)| {
         let without_comment = line.split("#").next().unwrap_or_default();
-        if without_comment.len() > 0 {
-            acc.push(String::from(without_comment.trim()));
+        if pending.is_empty() {
+            start = number;
+        }
+        if let Some(line) = continue_line(&mut pending, without_comment) {
+            if !line.is_empty() {
+                acc.push((start, line));
+            }
         }
         acc
-    }))
+    })
+}
+
+fn without_numbers(lines: NumberedLines) -> Vec<String> {
+    lines.into_iter().map(|(_, line)| line).collect()
 }
 
-fn line_to_chunk(line: &str) -> Result<Vec<String>, String> {
+fn line_to_chunk(line: &str) -> Result<Vec<String>, Error> {
     let (mut ret, scoped) = line.chars().fold((vec![String::default()], false), |(mut acc, mut scoped), c| {
         let accessor = acc.len() - 1;
         match (c, scoped) {
@@ -164,7 +215,7 @@ fn line_to_chunk(line: &str) -> Result<Vec<String>, String> {
         (acc, scoped)
     });
     if scoped {
-        Err(format!("unclosed quote in line: {line}"))?
+        Err(Error::UnclosedQuote(String::from(line)))?
     }
     Ok(ret.iter_mut()
         .map(|chunk| String::from(chunk.trim()))
@@ -173,9 +224,9 @@ fn line_to_chunk(line: &str) -> Result<Vec<String>, String> {
 }
 
 /* ---------- FILLING VARIABLES ---------- */
-fn def_var(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), String> {
+fn def_var(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), Error> {
     if chunks.len() == 0 {
-        Err(String::from("def line expect variable"))?
+        Err(Error::DefExpectVariable)?
     }
     let mut var = Variable::default();
     let mut var_name = String::default();
@@ -183,37 +234,37 @@ fn def_var(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silenc
         match i {
             0 => {
                 if chunk.len() != 1 {
-                    Err(format!("{chunk} is not a valid variable name"))?
+                    Err(Error::InvalidVariableName(chunk.clone()))?
                 } else if let Some(name) = chunk.chars().next() {
                     match name.is_alphabetic() {
                         true => var_name = chunk.clone(),
-                        _ => Err(format!("{chunk} is not a valid variable name"))?
+                        _ => Err(Error::InvalidVariableName(chunk.clone()))?
                     }
                 }
             },
             1 => {
                 if RESERVED_WORDS.contains(&chunk.as_str()) {
-                    Err(format!("{chunk} is a reserved word"))?
+                    Err(Error::ReservedWord(chunk.clone()))?
                 } else if let Some((k, _)) = variables.iter().find(|(k, v)| k.to_string() != var_name && (v.alias_true.clone().unwrap_or_default() == *chunk || v.alias_false.clone().unwrap_or_default() == *chunk)) {
-                    Err(format!("{chunk} is already an alias for {k}"))?
+                    Err(Error::AlreadyAlias(chunk.clone(), k.to_string()))?
                 } else if chunk.len() < 2 {
-                    Err(format!("{chunk} is too short to be an alias"))?
+                    Err(Error::AliasTooShort(chunk.clone()))?
                 }
                 var.alias_true = Some(String::from(chunk))
             },
             2 => {
                 if RESERVED_WORDS.contains(&chunk.as_str()) {
-                    Err(format!("{chunk} is a reserved word"))?
+                    Err(Error::ReservedWord(chunk.clone()))?
                 } else if let Some((k, _)) = variables.iter().find(|(k, v)| k.to_string() != var_name && (v.alias_true.clone().unwrap_or_default() == *chunk || v.alias_false.clone().unwrap_or_default() == *chunk)) {
-                    Err(format!("{chunk} is already an alias for {k}"))?
+                    Err(Error::AlreadyAlias(chunk.clone(), k.to_string()))?
                 } else if var.alias_true == Some(String::from(chunk)) {
-                    Err(format!("{chunk} is already the true alias"))?
+                    Err(Error::AlreadyTrueAlias(chunk.clone()))?
                 } else if chunk.len() < 2 {
-                    Err(format!("{chunk} is too short to be an alias"))?
+                    Err(Error::AliasTooShort(chunk.clone()))?
                 }
                 var.alias_false = Some(String::from(chunk))
             },
-            _ => Err(format!("unexpected {chunk} in def line"))?
+            _ => Err(Error::UnexpectedInDef(chunk.clone()))?
         }
     }
     if let Some((_, variable)) = variables.iter_mut().find(|(k, _)| k.to_string() == var_name) {
@@ -234,7 +285,7 @@ fn def_var(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silenc
     Ok(())
 }
 
-fn user_set(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), String> {
+fn user_set(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), Error> {
     for chunk in chunks.iter() {
         if let Some((k, var)) = variables.iter_mut().find(|(_, v)| v.alias_true == Some(String::from(chunk))) {
             if !silence {
@@ -264,7 +315,7 @@ fn user_set(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silen
                             }
                             variables.insert(c, Variable::insert());
                         },
-                        _ => Err(format!("{c} is an invalid variable name"))?
+                        _ => Err(Error::InvalidVariableName(c.to_string()))?
                     }
                 }
             }
@@ -290,7 +341,7 @@ fn aritmetic_to_string(aritmetic: &Vec<Vec<Operator>>, reverse: bool) -> String
 
 }
 
-fn check_splited(splited: &Vec<Vec<Operator>>) -> Result<(), String> {
+fn check_splited(splited: &Vec<Vec<Operator>>) -> Result<(), Error> {
     for part in splited {
         let mut last_op = None;
         for operator in part {
@@ -301,23 +352,23 @@ fn check_splited(splited: &Vec<Vec<Operator>>) -> Result<(), String> {
                 (Operator::Parentesis(false), Some(Operator::Parentesis(false)) | Some(Operator::Var(_))) => {
                     last_op = Some(operator.clone());
                 },
-                (_, None) => Err(format!("Unexpected operator {operator}"))?,
-                (_, Some(last_op)) => Err(format!("Unexpected operator {operator} following {last_op}"))?
+                (_, None) => Err(Error::UnexpectedOperator(operator.to_string()))?,
+                (_, Some(last_op)) => Err(Error::UnexpectedOperatorAfter(operator.to_string(), last_op.to_string()))?
             }
         }
     }
     Ok(())
 }
 
-fn insert_rule(rule: &Rule, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), String> {
+fn insert_rule(rule: &Rule, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), Error> {
     if rule.output.find_nodes(|n| match n {Operator::Var(_) | Operator::And => false, _ => true}).len() > 0 {
-        Err("output can only handle & and variables")?
+        Err(Error::OutputOperators)?
     }
     let outuput_var = rule.output.find_nodes(|ope| match ope {Operator::Var(_) => true, _ => false});
     let input_var = rule.input.find_nodes(|ope| match ope {Operator::Var(_) => true, _ => false});
     for var in input_var {
         if outuput_var.contains(&var) {
-            Err(format!("{var} is both in input and output"))?
+            Err(Error::InputAndOutput(var.to_string()))?
         }
     }
     if !silence {
@@ -336,7 +387,7 @@ fn insert_rule(rule: &Rule, variables: &mut HashMap<char, Variable>, silence: bo
     Ok(())
 }
 
-fn def_rules(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), String> {
+fn chunks_to_aritmetic(chunks: &[String], variables: &mut HashMap<char, Variable>, silence: bool) -> Result<Vec<Operator>, Error> {
     let mut aritmetic: Vec<Operator> = Vec::new();
     for chunk in chunks.iter() {
         if let Some(operator) = Operator::from_str(chunk) {
@@ -357,18 +408,93 @@ fn def_rules(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, sile
                     }
                     aritmetic.push(Operator::Var(string_to_char(chunk)));
                 },
-                _ => Err(format!("{chunk} is not a valid variable name "))?
+                _ => Err(Error::InvalidVariableName(chunk.clone()))?
             }
         } else {
-            Err(format!("{chunk} is unexpected in rule line"))?
+            Err(Error::UnexpectedInRule(chunk.clone()))?
         }
     }
-    let splited: Vec<Vec<Operator>> = aritmetic.split(|ope| *ope == Operator::IfAndOnlyIf || *ope == Operator::Then).map(|ope| ope.to_vec()).collect();
+    Ok(aritmetic)
+}
+
+/* ---------- KEYWORDS ---------- */
+// Keywords a catalog can name in its language with a `keyword.<name>` entry
+const KEYWORDS: [&str; 8] = ["and", "or", "xor", "not", "then", "if-and-only-if", "if", "def"];
+
+pub(crate) fn english_keyword(word: &str, lang: &Lang) -> Option<&'static str> {
+    KEYWORDS.iter().find(|keyword| lang.keyword(keyword).as_deref() == Some(word)).copied()
+}
+
+// Words that never name a variable, in english or in the language
+pub(crate) fn is_keyword(word: &str, lang: &Lang) -> bool {
+    RESERVED_WORDS.contains(&word) || english_keyword(word, lang).is_some() || Answer::from_str(word).is_some()
+}
+
+// Rewrites the keywords of the language in english, quoted aliases are left untouched.
+// One letter keywords, as the italian `e`, shadow the variable of the same name unless `letters` is false
+pub(crate) fn localize_line(line: &str, lang: &Lang, letters: bool) -> String {
+    let translate = |word: &str, scoped: bool| match (scoped, english_keyword(word, lang)) {
+        (false, Some(keyword)) if letters || word.chars().count() > 1 => String::from(keyword),
+        _ => String::from(word)
+    };
+    let mut localized = String::new();
+    let mut word = String::new();
+    let mut scoped = false;
+    for c in line.chars() {
+        match c == '"' || (!scoped && (c.is_whitespace() || "()!+^&|=<>?".contains(c))) {
+            true => {
+                localized.push_str(&translate(&word, scoped));
+                localized.push(c);
+                word.clear();
+                if c == '"' {
+                    scoped = !scoped;
+                }
+            },
+            false => word.push(c)
+        }
+    }
+    localized + &translate(&word, scoped)
+}
+
+fn split_aritmetic(aritmetic: &[Operator]) -> Vec<Vec<Operator>> {
+    aritmetic.split(|ope| *ope == Operator::IfAndOnlyIf || *ope == Operator::Then).map(|ope| ope.to_vec()).collect()
+}
+
+// Only reports the mistakes that can be seen while the rule is still being typed:
+// unbalanced parentheses and invalid operator sequences
+pub(crate) fn check_rule_syntax(line: &str, variables: &HashMap<char, Variable>) -> Result<(), Error> {
+    let chunks = match line_to_chunk(line) {
+        Ok(chunks) => chunks,
+        Err(_) => return Ok(())
+    };
+    let body = match chunks.first().map(|first| first.as_str()) {
+        Some("if") => &chunks[1..],
+        _ => &chunks[..]
+    };
+    let aritmetic = match chunks_to_aritmetic(body, &mut variables.clone(), true) {
+        Ok(aritmetic) => aritmetic,
+        Err(_) => return Ok(())
+    };
+    let depth = aritmetic.iter().try_fold(0, |depth, ope| match ope {
+        Operator::Parentesis(true) => Ok(depth + 1),
+        Operator::Parentesis(false) if depth == 0 => Err(Error::UnexpectedClosing),
+        Operator::Parentesis(false) => Ok(depth - 1),
+        _ => Ok(depth)
+    })?;
+    if depth > 0 {
+        Err(Error::UnclosedDelimiter)?
+    }
+    check_splited(&split_aritmetic(&aritmetic))
+}
+
+fn def_rules(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), Error> {
+    let aritmetic = chunks_to_aritmetic(chunks, variables, silence)?;
+    let splited: Vec<Vec<Operator>> = split_aritmetic(&aritmetic);
     match splited.len() {
-        0 => Err("unexpected error")?,
-        1 => Err("expected => or <=> operator")?,
+        0 => Err(Error::Unexpected)?,
+        1 => Err(Error::ExpectedImplication)?,
         2 => (),
-        _ => Err("expected only 1 => or <=> operator")?
+        _ => Err(Error::SeveralImplications)?
     }
     check_splited(&splited)?;
     let rule = Rule {
@@ -388,7 +514,7 @@ fn def_rules(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, sile
     Ok(())
 }
 
-fn requests(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), String> {
+fn requests(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silence: bool) -> Result<(), Error> {
     for chunk in chunks.iter() {
         if let Some((_, var)) = variables.iter_mut().find(|(_, v)| v.alias_true == Some(String::from(chunk))) {
             var.requested = true;
@@ -412,7 +538,7 @@ fn requests(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silen
                                 println!("{}", format!("+? {c}").green());
                             }
                         },
-                        _ => Err(format!("{c} is not a valid variable name"))?,
+                        _ => Err(Error::InvalidVariableName(c.to_string()))?,
                     };
                 }
             }
@@ -422,12 +548,228 @@ fn requests(chunks: &Vec<String>, variables: &mut HashMap<char, Variable>, silen
     Ok(())
 }
 
-pub fn parse_line(old_variables: &mut HashMap<char, Variable>, line: String, restricted: bool, silence: bool) -> Result<(), String> {
+/* ---------- COMMAND LINE FACTS ---------- */
+// The variable named by a letter or an alias, with true when the name is its false alias
+pub(crate) fn find_named(variables: &HashMap<char, Variable>, name: &str) -> Option<(char, bool)> {
+    variables.iter().find_map(|(k, v)| match (k.to_string() == name || v.alias_true.as_deref() == Some(name), v.alias_false.as_deref() == Some(name)) {
+        (true, _) => Some((*k, false)),
+        (_, true) => Some((*k, true)),
+        _ => None
+    })
+}
+
+// Sets the variables on top of the `=` lines, an unknown letter is created as a new fact.
+// Setting a false alias locks its variable to false, unsetting clears the fact whichever name is used
+pub fn set_facts(variables: &mut HashMap<char, Variable>, names: &[String], set: bool) -> Result<(), Error> {
+    for name in names {
+        let (key, negated) = match find_named(variables, name) {
+            Some(found) => found,
+            None if name.chars().count() == 1 && string_to_char(name).is_alphabetic() => {
+                variables.insert(string_to_char(name), Variable::insert());
+                (string_to_char(name), false)
+            },
+            None => Err(Error::CannotFindVariable(name.clone()))?
+        };
+        if let Some(variable) = variables.get_mut(&key) {
+            variable.value = set && !negated;
+            variable.locked = set;
+        }
+    }
+    Ok(())
+}
+
+pub fn add_queries(variables: &mut HashMap<char, Variable>, names: &[String]) -> Result<(), Error> {
+    for name in names {
+        let key = match find_named(variables, name) {
+            Some((key, _)) => key,
+            None if name.chars().count() == 1 && string_to_char(name).is_alphabetic() => string_to_char(name),
+            None => Err(Error::CannotFindVariable(name.clone()))?
+        };
+        variables.entry(key).or_insert(Variable::request()).requested = true;
+    }
+    Ok(())
+}
+
+/* ---------- EXPECTATIONS ---------- */
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expectation {
+    Value(String, Answer),
+    Error(String)
+}
+
+fn def_expectation(chunks: &[String]) -> Result<Expectation, Error> {
+    match chunks {
+        [kind, message] if kind == "error" => Ok(Expectation::Error(message.clone())),
+        [name, value] => match Answer::from_str(value) {
+            Some(answer) => Ok(Expectation::Value(name.clone(), answer)),
+            None => Err(Error::InvalidAnswer(value.to_string()))?
+        },
+        _ => Err(Error::ExpectLine)
+    }
+}
+
+fn lines_expectations(lines: &[String]) -> Vec<Expectation> {
+    lines
+        .iter()
+        .filter_map(|line| line_to_chunk(line).ok())
+        .filter(|chunks| chunks.first().map(|first| first.as_str()) == Some("expect"))
+        .filter_map(|chunks| def_expectation(&chunks[1..]).ok())
+        .collect()
+}
+
+// Expectations outside the scenarios, or of every line when the scenarios do not parse
+pub(crate) fn source_expectations(contents: &str) -> Vec<Expectation> {
+    let lines = split_lines(contents);
+    match split_scenarios(lines.clone()) {
+        Ok((top_level, _)) => lines_expectations(&without_numbers(top_level)),
+        Err(_) => lines_expectations(&without_numbers(lines))
+    }
+}
+
+pub(crate) fn scenario_expectations(scenario: &Scenario) -> Vec<Expectation> {
+    lines_expectations(&scenario.lines)
+}
+
+/* ---------- SCENARIOS ---------- */
+fn find_unquoted(line: &str, searched: char) -> Option<usize> {
+    let mut scoped = false;
+    for (i, c) in line.char_indices() {
+        match (c, scoped) {
+            ('"', _) => scoped = !scoped,
+            (c, false) if c == searched => return Some(i),
+            _ => ()
+        }
+    }
+    None
+}
+
+// Splits an inline body such as `=B ?CY expect C true` into one statement per `=`, `?` or `expect`
+fn split_statements(body: &str) -> Vec<String> {
+    let mut statements: Vec<String> = Vec::new();
+    let (mut scoped, mut last) = (false, ' ');
+    for (i, c) in body.char_indices() {
+        let is_expect = body[i..].strip_prefix("expect").map(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)).unwrap_or(false);
+        match (!scoped && last.is_whitespace() && (c == '=' || c == '?' || is_expect), statements.last_mut()) {
+            (false, Some(statement)) => statement.push(c),
+            _ => statements.push(String::from(c))
+        }
+        scoped ^= c == '"';
+        last = c;
+    }
+    statements
+        .iter()
+        .map(|statement| String::from(statement.trim()))
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
+fn check_scenario_line(name: &str, line: &str) -> Result<(), Error> {
+    match line_to_chunk(line)?.first().map(|first| first.as_str()) {
+        Some("expect") | None => Ok(()),
+        Some(first) if first.starts_with('=') || first.starts_with('?') => Ok(()),
+        Some(_) => Err(Error::ScenarioStatement(name.to_string(), line.to_string()))
+    }
+}
+
+// Files one line in the scenario being read, a new one or the top level, which gets the line back
+fn split_scenario_line(line: String, current: &mut Option<Scenario>, scenarios: &mut Vec<Scenario>) -> Result<Option<String>, Error> {
+    let is_header = line_to_chunk(&line).map(|chunks| chunks.first().map(|first| first.as_str()) == Some("scenario")).unwrap_or(false);
+    match (current.as_mut(), is_header) {
+        (Some(scenario), true) => Err(Error::UnclosedScenarioBefore(scenario.name.clone(), line.to_string()))?,
+        (Some(scenario), false) => {
+            if line == "}" {
+                scenarios.push(scenario.clone());
+                *current = None;
+            } else {
+                check_scenario_line(&scenario.name, &line)?;
+                scenario.lines.push(line);
+            }
+        },
+        (None, true) => {
+            let chunks = line_to_chunk(&line)?;
+            let name = match chunks.get(1) {
+                Some(name) if name != "{" => name.clone(),
+                _ => Err(Error::ScenarioName(line.to_string()))?
+            };
+            let body = match find_unquoted(&line, '{') {
+                Some(i) => &line[i + 1..],
+                None => Err(Error::ScenarioBrace(name.to_string()))?
+            };
+            let mut scenario = Scenario { name, lines: Vec::new() };
+            match find_unquoted(body, '}') {
+                Some(i) => {
+                    if !body[i + 1..].trim().is_empty() {
+                        Err(Error::UnexpectedAfterScenario(body[i + 1..].trim().to_string(), scenario.name.clone()))?
+                    }
+                    for statement in split_statements(&body[..i]) {
+                        check_scenario_line(&scenario.name, &statement)?;
+                        scenario.lines.push(statement);
+                    }
+                    scenarios.push(scenario);
+                },
+                None => {
+                    for statement in split_statements(body) {
+                        check_scenario_line(&scenario.name, &statement)?;
+                        scenario.lines.push(statement);
+                    }
+                    *current = Some(scenario);
+                }
+            }
+        },
+        (None, false) => return Ok(Some(line))
+    }
+    Ok(None)
+}
+
+// Errors come with the index of their line
+fn split_scenarios(lines: NumberedLines) -> Result<(NumberedLines, Vec<Scenario>), (usize, Error)> {
+    let mut top_level: NumberedLines = Vec::new();
+    let mut scenarios: Vec<Scenario> = Vec::new();
+    let mut current: Option<Scenario> = None;
+    let mut opened = 0;
+    for (number, line) in lines {
+        let reading = current.is_some();
+        if let Some(line) = split_scenario_line(line, &mut current, &mut scenarios).map_err(|e| (number, e))? {
+            top_level.push((number, line));
+        }
+        if !reading && current.is_some() {
+            opened = number;
+        }
+    }
+    if let Some(scenario) = current {
+        Err((opened, Error::UnclosedScenario(scenario.name.clone())))?
+    }
+    Ok((top_level, scenarios))
+}
+
+pub(crate) fn source_scenarios(contents: &str) -> Result<Vec<Scenario>, Error> {
+    split_scenarios(split_lines(contents)).map(|(_, scenarios)| scenarios).map_err(|(_, e)| e)
+}
+
+pub fn apply_scenario(variables: &mut HashMap<char, Variable>, scenario: &Scenario, silence: bool) -> Result<(), Error> {
+    for (_, variable) in variables.iter_mut() {
+        variable.value = false;
+        variable.locked = false;
+        variable.requested = false;
+    }
+    for line in scenario.lines.iter() {
+        parse_line(variables, line.clone(), false, silence)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_line(old_variables: &mut HashMap<char, Variable>, line: String, restricted: bool, silence: bool) -> Result<(), Error> {
     let mut variables = (*old_variables).clone();
     let mut chunks = line_to_chunk(&line)?;
     if let Some(first) = chunks.iter().next() {
         match (first.as_str(), string_to_char(first)){
             ("def", _) => def_var(&chunks[1..].to_vec(), &mut variables, silence)?,
+            ("expect", _) => {
+                if restricted {
+                    Err(Error::ExpectOnlyInFiles)?
+                }
+                def_expectation(&chunks[1..])?;
+            },
             ("if", _) => def_rules(&chunks[1..].to_vec(), &mut variables, silence)?,
             ("=", _) => user_set(&chunks[1..].to_vec(), &mut variables, silence)?,
             (_, '=') => {
@@ -445,7 +787,7 @@ pub fn parse_line(old_variables: &mut HashMap<char, Variable>, line: String, res
             },
             _ => {
                 if restricted {
-                    Err(format!("Expected one of [=, ?, def, if] found {line}"))?
+                    Err(Error::ExpectedStatement(line.to_string()))?
                 } else {
                     def_rules(&chunks, &mut variables, silence)?
                 }
@@ -456,10 +798,121 @@ pub fn parse_line(old_variables: &mut HashMap<char, Variable>, line: String, res
     Ok(())
 }
 
-pub fn fill_maps(variables: &mut HashMap<char, Variable>, file: &str, silence: bool) -> Result<(), String> {
-    let lines = to_splited_string(file)?;
-    for line in lines {
-       parse_line(variables, line, false, silence)?;
+// A `lang <code>` line selects the keywords of the following lines, the active language otherwise.
+// Without it a file keeps its one letter variables, the active language may come from the environment.
+// The file is read once, its scenarios are returned to be applied on copies of the variables.
+// A `.json` file is a knowledge base written by `export json`, its variables must be new ones
+pub fn fill_maps(variables: &mut HashMap<char, Variable>, file: &str, silence: bool) -> Result<Vec<Scenario>, Error> {
+    if !file.ends_with(".json") {
+        return fill_source(variables, &read_source(file)?, silence)
     }
-    Ok(())
-}
\ No newline at end of file
+    for (key, variable) in from_json(&read_source(file)?)? {
+        if variables.contains_key(&key) {
+            Err(Error::VariableExists(key.to_string()))?
+        }
+        variables.insert(key, variable);
+    }
+    Ok(Vec::new())
+}
+
+// Same as `fill_maps` for rules that are not in a file
+pub(crate) fn fill_source(variables: &mut HashMap<char, Variable>, contents: &str, silence: bool) -> Result<Vec<Scenario>, Error> {
+    let (scenarios, errors) = parse_source(variables, contents, silence, false);
+    match errors.into_iter().next() {
+        Some((_, e)) => Err(e),
+        None => Ok(scenarios)
+    }
+}
+
+// Every error of rules with the index of its line, a line in error is skipped and the following ones are still read
+pub(crate) fn source_errors(variables: &mut HashMap<char, Variable>, contents: &str) -> Vec<(usize, Error)> {
+    parse_source(variables, contents, true, true).1
+}
+
+fn parse_source(variables: &mut HashMap<char, Variable>, contents: &str, silence: bool, keep_going: bool) -> (Vec<Scenario>, Vec<(usize, Error)>) {
+    let (lines, scenarios) = match split_scenarios(split_lines(contents)) {
+        Ok(split) => split,
+        Err(error) => return (Vec::new(), vec![error])
+    };
+    let mut errors = Vec::new();
+    let mut keywords = Lang::current();
+    let mut declared = false;
+    for (number, line) in lines {
+        let parsed = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["lang", code] => Lang::from_code(code).map(|lang| {
+                keywords = lang;
+                declared = true;
+            }),
+            _ => parse_line(variables, localize_line(&line, &keywords, declared), false, silence)
+        };
+        if let Err(e) = parsed {
+            errors.push((number, e));
+            if !keep_going {
+                break
+            }
+        }
+    }
+    (scenarios, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| String::from(*line)).collect()
+    }
+
+    #[test]
+    fn facts_are_set_and_cleared_by_any_name() {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        fill_source(&mut variables, "def R \"It rains\" \"It's dry\"\nR => W\n=R\n", true).unwrap();
+        set_facts(&mut variables, &strings(&["It's dry"]), true).unwrap();
+        assert!(!variables[&'R'].value && variables[&'R'].locked);
+        set_facts(&mut variables, &strings(&["It rains"]), true).unwrap();
+        assert!(variables[&'R'].value && variables[&'R'].locked);
+        set_facts(&mut variables, &strings(&["It's dry"]), false).unwrap();
+        assert!(!variables[&'R'].value && !variables[&'R'].locked);
+        set_facts(&mut variables, &strings(&["Z"]), true).unwrap();
+        assert!(variables[&'Z'].value && variables[&'Z'].locked);
+        assert!(set_facts(&mut variables, &strings(&["snow"]), true).is_err());
+    }
+
+    #[test]
+    fn one_letter_keywords_only_shadow_declared_languages() {
+        let lang = Lang::from_code("it").unwrap();
+        assert_eq!(localize_line("se A e B allora \"e\"", &lang, true), "if A and B then \"e\"");
+        assert_eq!(localize_line("se A e non o allora B", &lang, false), "if A e not o then B");
+    }
+
+    #[test]
+    fn lines_continue_on_backslash_and_open_parenthesis() {
+        let mut pending = String::new();
+        assert_eq!(continue_line(&mut pending, "A + \\"), None);
+        assert_eq!(continue_line(&mut pending, "(B |"), None);
+        assert_eq!(continue_line(&mut pending, "  C) => D"), Some(String::from("A + (B | C) => D")));
+        assert_eq!(continue_line(&mut pending, "def E \"(\""), Some(String::from("def E \"(\"")));
+        assert_eq!(continue_line(&mut pending, "(A"), None);
+        assert_eq!(continue_line(&mut pending, ""), Some(String::from("(A")));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn inline_scenarios_split_on_expect() {
+        assert_eq!(split_statements(" =B ?C expect C true "), strings(&["=B", "?C", "expect C true"]));
+        assert_eq!(split_statements("=A expect error \"a ?b =c\""), strings(&["=A", "expect error \"a ?b =c\""]));
+        assert_eq!(split_statements("=A ?expected"), strings(&["=A", "?expected"]));
+    }
+
+    #[test]
+    fn scenarios_come_with_the_rules() {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        let scenarios = fill_source(&mut variables, "A => C\nscenario one { =A ?C expect C true }\n", true).unwrap();
+        assert_eq!(scenarios.len(), 1);
+        assert_eq!(scenarios[0].lines, strings(&["=A", "?C", "expect C true"]));
+        assert_eq!(scenario_expectations(&scenarios[0]), vec![Expectation::Value(String::from("C"), Answer::True)]);
+        let mut applied = variables.clone();
+        apply_scenario(&mut applied, &scenarios[0], true).unwrap();
+        assert!(applied[&'A'].value && applied[&'C'].requested);
+    }
+}
@@ -0,0 +1,353 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use colored::{Colorize, ColoredString};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use crate::models::{Variable, Operator};
+use crate::translate::available_langs;
+use crate::parsing::{check_rule_syntax, is_continued, localize_line, english_keyword};
+use crate::translate::Lang;
+use crate::export::format_names;
+
+// Argument signature of each interactive command, as printed by `help`
+const SIGNATURES: [(&str, &str); 32] = [
+    ("help", "<?Command ...>"),
+    ("quit", ""),
+    ("language", "<Lang>"),
+    ("lang", "<Lang>"),
+    ("trace", ""),
+    ("reset", ""),
+    ("variables", ""),
+    ("rules", ""),
+    ("clear", ""),
+    ("file", "<Path>"),
+    ("run", "<?Variable ...>"),
+    ("remove", "[all, var, rule, ?, =]"),
+    ("remove all", ""),
+    ("remove var", "<Variable>"),
+    ("remove rule", "<Index>"),
+    ("remove ?", "<?Variable ...>"),
+    ("remove =", "<?Variable ...>"),
+    ("=", "<Variable ...>"),
+    ("?", "<Variable ...>"),
+    ("def", "<Variable> <?alias true> <?alias false>"),
+    ("if", "<Rule>"),
+    ("export", "[graph, json, dimacs, smt2, prolog]"),
+    ("export graph", "<?Path>"),
+    ("export json", "<?Path>"),
+    ("export dimacs", "<?Path>"),
+    ("export smt2", "<?Path>"),
+    ("export prolog", "<?Path>"),
+    ("import", "[json]"),
+    ("import json", "<Path>"),
+    ("journal", "<?Path|off>"),
+    ("replay", "<Path>"),
+    ("watch", "[on, off]")
+];
+
+pub(crate) fn signature(command: &str) -> &'static str {
+    SIGNATURES.iter().find(|(c, _)| *c == command).map(|(_, s)| *s).unwrap_or_default()
+}
+
+pub(crate) struct PromptHelper {
+    names: Vec<String>,
+    variables: HashMap<char, Variable>,
+    filename: FilenameCompleter
+}
+
+impl PromptHelper {
+    pub fn new(variables: &HashMap<char, Variable>) -> Self {
+        let mut helper = PromptHelper {
+            names: Vec::new(),
+            variables: HashMap::new(),
+            filename: FilenameCompleter::new()
+        };
+        helper.set_variables(variables);
+        helper
+    }
+
+    pub(crate) fn set_variables(&mut self, variables: &HashMap<char, Variable>) {
+        let mut keys: Vec<&char> = variables.keys().collect();
+        keys.sort();
+        self.variables = variables.clone();
+        self.names = keys.iter().map(|k| k.to_string()).collect();
+        for key in keys {
+            for alias in [&variables[key].alias_true, &variables[key].alias_false].into_iter().flatten() {
+                self.names.push(format!("\"{alias}\""));
+            }
+        }
+    }
+}
+
+// Start of the word under the cursor, an unclosed quote starts a word
+fn word_start(line: &str) -> usize {
+    let (start, _) = line.char_indices().fold((0, false), |(start, scoped), (i, c)| {
+        match (c, scoped) {
+            ('"', false) => (i, true),
+            ('"', true) => (start, false),
+            (' ', false) => (i + 1, false),
+            _ => (start, scoped)
+        }
+    });
+    start
+}
+
+fn candidates(word: &str, choices: &[String]) -> Vec<Pair> {
+    let lower = word.to_lowercase();
+    choices
+        .iter()
+        .filter(|choice| choice.to_lowercase().starts_with(&lower))
+        .map(|choice| Pair { display: choice.clone(), replacement: choice.clone() })
+        .collect()
+}
+
+impl Completer for PromptHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = word_start(&line[..pos]);
+        let word = &line[start..pos];
+        let before: Vec<String> = line[..start].split_whitespace().map(|w| w.to_lowercase()).collect();
+        let commands: Vec<String> = SIGNATURES.iter().filter(|(c, _)| !c.contains(' ')).map(|(c, _)| String::from(*c)).collect();
+        let to_strings = |words: &[&str]| words.iter().map(|w| String::from(*w)).collect::<Vec<String>>();
+        let choices = match (before.first().map(|w| w.as_str()), before.len()) {
+            (None, _) => commands.into_iter().chain(self.names.iter().cloned()).collect(),
+            (Some("help"), _) => commands,
+            (Some("lang" | "language"), 1) => available_langs(),
+            (Some("file" | "replay" | "journal"), 1) | (Some("export" | "import"), 2) => return self.filename.complete_path(line, pos),
+            (Some("export"), 1) => format_names().split(", ").map(String::from).collect(),
+            (Some("import"), 1) => to_strings(&["json"]),
+            (Some("watch"), 1) => to_strings(&["on", "off"]),
+            (Some("remove" | "del" | "delete"), 1) => to_strings(&["all", "var", "rule", "?", "="]),
+            (Some("remove" | "del" | "delete"), _) if before.get(1).map(|w| w.as_str()) == Some("rule") => Vec::new(),
+            (Some("lang" | "language" | "file" | "export" | "import" | "quit" | "trace" | "reset" | "clear" | "replay" | "journal" | "watch"), _) => Vec::new(),
+            _ => self.names.clone()
+        };
+        Ok((start, candidates(word, &choices)))
+    }
+}
+
+impl Hinter for PromptHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None
+        }
+        let typed = line.trim_start().to_lowercase();
+        if let Some((_, signature)) = SIGNATURES.iter().find(|(c, _)| *c == typed.trim_end()) {
+            return match (signature.is_empty(), typed.ends_with(' ')) {
+                (true, _) => None,
+                (false, true) => Some(String::from(*signature)),
+                (false, false) => Some(format!(" {signature}"))
+            }
+        }
+        let mut matching: Vec<&(&str, &str)> = SIGNATURES.iter().filter(|(c, _)| !typed.is_empty() && c.starts_with(&typed) && (!c.contains(' ') || typed.contains(' '))).collect();
+        matching.sort_by_key(|(c, _)| c.len());
+        match matching.first() {
+            Some((command, signature)) if matching.iter().all(|(c, _)| c.starts_with(command)) => {
+                match signature.is_empty() {
+                    true => Some(String::from(&command[typed.len()..])),
+                    false => Some(format!("{} {signature}", &command[typed.len()..]))
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+/* ---------- HIGHLIGHTING ---------- */
+#[derive(PartialEq)]
+enum CharKind {
+    Space,
+    Comparison,
+    Word,
+    Symbol
+}
+
+impl CharKind {
+    fn of(c: char) -> CharKind {
+        match c {
+            ' ' | '\t' => CharKind::Space,
+            '=' | '<' | '>' => CharKind::Comparison,
+            '!' | '+' | '^' | '&' | '(' | ')' | '|' | '?' => CharKind::Symbol,
+            _ => CharKind::Word
+        }
+    }
+}
+
+// Splits the line in words, quoted aliases and operators while keeping the spaces
+fn tokenize(line: &str) -> Vec<String> {
+    let (tokens, _) = line.chars().fold((Vec::new(), false), |(mut acc, scoped): (Vec<String>, bool), c| {
+        let last_kind = acc.last().and_then(|token: &String| token.chars().last()).map(CharKind::of);
+        match (c, scoped, acc.last_mut()) {
+            ('"', false, _) => {
+                acc.push(String::from(c));
+                return (acc, true)
+            },
+            ('"', true, Some(token)) => {
+                token.push(c);
+                acc.push(String::new());
+                return (acc, false)
+            },
+            (_, true, Some(token)) => token.push(c),
+            (_, false, Some(token)) if last_kind == Some(CharKind::of(c)) && CharKind::of(c) != CharKind::Symbol => token.push(c),
+            _ => acc.push(String::from(c))
+        }
+        (acc, scoped)
+    });
+    tokens.into_iter().filter(|token| !token.is_empty()).collect()
+}
+
+impl PromptHelper {
+    fn color_token(&self, token: &str, first: bool, facts: bool) -> ColoredString {
+        let token_en = english_keyword(token, &Lang::current()).unwrap_or(token);
+        let is_command = ["if", "def"].contains(&token_en) || SIGNATURES.iter().any(|(c, _)| *c == token.to_lowercase()) || ["exec", "execute", "del", "delete", "var", "rule"].contains(&token.to_lowercase().as_str());
+        if token.starts_with('"') {
+            let alias = token.trim_matches('"');
+            return match self.variables.values().find(|v| v.alias_true.as_deref() == Some(alias) || v.alias_false.as_deref() == Some(alias)) {
+                Some(v) if v.alias_true.as_deref() == Some(alias) => token.green(),
+                Some(_) => token.red(),
+                None => token.normal()
+            }
+        }
+        match (first && is_command, Operator::from_str(&token_en.to_lowercase())) {
+            (true, _) => token.blue().bold(),
+            (false, Some(_)) => token.purple(),
+            _ if token.chars().all(|c| c.is_alphabetic()) && (facts || token.chars().count() == 1) => token.yellow().bold(),
+            _ => token.normal()
+        }
+    }
+}
+
+impl Highlighter for PromptHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let tokens = tokenize(line);
+        let first_word = tokens.iter().position(|token| !token.trim().is_empty());
+        let line_en = localize_line(line, &Lang::current(), false);
+        let is_rule = line_en.split_whitespace().next().map(|first| first.to_lowercase() == "if").unwrap_or(false);
+        // an unfinished line is completed on the secondary prompt
+        let invalid = is_rule && !is_continued(line) && check_rule_syntax(&line_en, &self.variables).is_err();
+        // letters following `=` or `?` are a list of variables, as in `=AB`
+        let facts = first_word.map(|i| tokens[i] == "=" || tokens[i] == "?").unwrap_or(false);
+        let highlighted = tokens.iter().enumerate().map(|(i, token)| {
+            let colored = self.color_token(token, Some(i) == first_word, facts);
+            match invalid && !token.trim().is_empty() {
+                true => colored.underline().to_string(),
+                false => colored.to_string()
+            }
+        }).collect::<String>();
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+}
+
+impl Validator for PromptHelper {}
+
+impl Helper for PromptHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+    use crate::parsing::fill_source;
+    use crate::utils::COLORS;
+    use colored::control::{set_override, unset_override};
+
+    fn helper() -> PromptHelper {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        fill_source(&mut variables, "def A \"sun\" \"moon\"\nA => B\n", true).unwrap();
+        PromptHelper::new(&variables)
+    }
+
+    fn completions(helper: &PromptHelper, line: &str) -> (usize, Vec<String>) {
+        let history = History::new();
+        let (start, pairs) = helper.complete(line, line.len(), &Context::new(&history)).unwrap();
+        (start, pairs.into_iter().map(|pair| pair.replacement).collect())
+    }
+
+    #[test]
+    fn words_start_after_spaces_or_at_an_open_quote() {
+        assert_eq!(word_start("remove va"), 7);
+        assert_eq!(word_start("if \"su"), 3);
+        assert_eq!(word_start("if \"red sun\" an"), 13);
+    }
+
+    #[test]
+    fn completion_follows_the_command() {
+        let helper = helper();
+        assert_eq!(completions(&helper, "rem"), (0, vec![String::from("remove")]));
+        assert_eq!(completions(&helper, "remove v"), (7, vec![String::from("var")]));
+        assert_eq!(completions(&helper, "= \"s"), (2, vec![String::from("\"sun\"")]));
+        assert_eq!(completions(&helper, "watch o").1, vec![String::from("on"), String::from("off")]);
+        assert!(completions(&helper, "remove rule ").1.is_empty());
+        assert!(completions(&helper, "export ").1.contains(&String::from("prolog")));
+    }
+
+    #[test]
+    fn hints_give_the_rest_of_the_command_and_its_arguments() {
+        let helper = helper();
+        let history = History::new();
+        let hint = |line: &str| helper.hint(line, line.len(), &Context::new(&history));
+        assert_eq!(hint("repl"), Some(String::from("ay <Path>")));
+        assert_eq!(hint("replay"), Some(String::from(" <Path>")));
+        assert_eq!(hint("replay "), Some(String::from("<Path>")));
+        assert_eq!(hint("quit"), None);
+        assert_eq!(hint("re"), None);
+        assert_eq!(hint("run A"), None);
+    }
+
+    #[test]
+    fn tokens_keep_quotes_operators_and_spaces() {
+        assert_eq!(tokenize("if \"red sun\" <=> !B"), ["if", " ", "\"red sun\"", " ", "<=>", " ", "!", "B"]);
+        assert_eq!(tokenize("=AB  ?C"), ["=", "AB", "  ", "?", "C"]);
+        assert_eq!(tokenize("(A|B)"), ["(", "A", "|", "B", ")"]);
+    }
+
+    #[test]
+    fn highlighting_only_adds_colors() {
+        let helper = helper();
+        for line in ["if \"sun\" & B then C", "if A => (B", "=AB ?C", "remove var A"] {
+            let highlighted = helper.highlight(line, 0).to_string();
+            let mut plain = String::new();
+            let mut escaped = false;
+            for c in highlighted.chars() {
+                match (c, escaped) {
+                    ('\x1b', _) => escaped = true,
+                    ('m', true) => escaped = false,
+                    (_, true) => (),
+                    (c, false) => plain.push(c)
+                }
+            }
+            assert_eq!(plain, line);
+        }
+    }
+
+    #[test]
+    fn invalid_rules_are_underlined() {
+        let _colors = COLORS.lock().unwrap_or_else(|e| e.into_inner());
+        set_override(true);
+        let helper = helper();
+        // the underline is the SGR parameter 4, often combined with the color of the token
+        let underlined = |line: &str| helper.highlight(line, 0).split("\x1b[").skip(1).any(|sequence| {
+            sequence.split('m').next().unwrap_or_default().split(';').any(|parameter| parameter == "4")
+        });
+        assert!(underlined("if A => B)"));
+        assert!(underlined("if A & | B => C"));
+        assert!(!underlined("if \"sun\" & B then C"));
+        assert!(!underlined("if A => B"));
+        // an open parenthesis at the end continues the rule on the next line
+        assert!(!underlined("if A => (B"));
+        unset_override();
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::spawn;
+use colored::Colorize;
+use colored::control::set_override;
+use serde_json::{json, Map, Value};
+use crate::interactive::Session;
+use crate::leakser::Flag;
+use crate::models::{Variable, Answer};
+use crate::parsing::{fill_maps, fill_source, source_errors, parse_line, localize_line, set_facts, add_queries, find_named};
+use crate::algo::search_query;
+use crate::export::unique_rules;
+use crate::utils::{history_text, read_source};
+use crate::watch::query_results;
+use crate::translate::{Error, Lang};
+
+// Codes of the JSON-RPC 2.0 specification, engine errors such as a bad rule have their own
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const ENGINE_ERROR: i64 = 1;
+
+#[derive(PartialEq, Clone)]
+pub enum Address {
+    Unix(String),
+    Tcp(u16)
+}
+
+enum Failure {
+    Method(String),
+    Params(Error),
+    Engine(Error)
+}
+
+impl Failure {
+    fn code_message(self, lang: &Lang) -> (i64, String) {
+        match self {
+            Failure::Method(method) => (METHOD_NOT_FOUND, Error::MethodNotFound(method).message(lang)),
+            Failure::Params(e) => (INVALID_PARAMS, e.message(lang)),
+            Failure::Engine(e) => (ENGINE_ERROR, e.message(lang))
+        }
+    }
+}
+
+/* ---------- PARAMS ---------- */
+// A list of strings, a single string is a list of one
+fn strings(params: &Value, key: &str) -> Result<Option<Vec<String>>, Failure> {
+    match params.get(key) {
+        None => Ok(None),
+        Some(Value::String(string)) => Ok(Some(vec![string.clone()])),
+        Some(Value::Array(values)) => values.iter()
+            .map(|value| value.as_str().map(String::from).ok_or(Failure::Params(Error::NotStrings(String::from(key)))))
+            .collect::<Result<Vec<String>, Failure>>()
+            .map(Some),
+        Some(_) => Err(Failure::Params(Error::NotStringOrList(String::from(key))))
+    }
+}
+
+fn required_strings(params: &Value, key: &str) -> Result<Vec<String>, Failure> {
+    strings(params, key)?.ok_or(Failure::Params(Error::MissingParam(String::from(key))))
+}
+
+/* ---------- METHODS ---------- */
+fn summary(variables: &HashMap<char, Variable>) -> Value {
+    json!({ "variables": variables.len(), "rules": unique_rules(variables).len() })
+}
+
+fn variable_json(key: char, variable: &Variable) -> Value {
+    json!({
+        "name": key.to_string(),
+        "value": variable.value,
+        "locked": variable.locked,
+        "requested": variable.requested,
+        "alias_true": variable.alias_true,
+        "alias_false": variable.alias_false,
+        "rules": variable.rules.iter().map(|rule| rule.to_string().trim().to_string()).collect::<Vec<String>>()
+    })
+}
+
+// Only files in the directories of the maps of the command line are read. An error gives the
+// number of its line and never its text, which would show any file to a client
+fn load_file(variables: &mut HashMap<char, Variable>, file: &str, roots: &[PathBuf]) -> Result<(), Error> {
+    match Path::new(file).canonicalize() {
+        Ok(path) if roots.iter().any(|root| path.starts_with(root)) => (),
+        _ => Err(Error::FileNotServed(String::from(file)))?
+    }
+    let contents = read_source(file)?;
+    if let Some((line, _)) = source_errors(&mut variables.clone(), &contents).first() {
+        Err(Error::in_file(file, Error::InvalidLine((line + 1).to_string())))?
+    }
+    fill_source(variables, &contents, true).map(|_| ())
+}
+
+// Loads files, then rules given as text, in place of the current knowledge base
+fn load(session: &mut Session, roots: &[PathBuf], params: &Value) -> Result<Value, Failure> {
+    let files = strings(params, "files")?.unwrap_or_default();
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    for file in files.iter() {
+        load_file(&mut variables, file, roots).map_err(Failure::Engine)?;
+    }
+    if let Some(rules) = params.get("rules").and_then(Value::as_str) {
+        fill_source(&mut variables, rules, true).map_err(Failure::Engine)?;
+    }
+    session.files = files;
+    session.variables = variables;
+    Ok(summary(&session.variables))
+}
+
+// Each method works on a copy, a failing call leaves the session as it was
+fn call(session: &mut Session, roots: &[PathBuf], method: &str, params: &Value) -> Result<Value, Failure> {
+    let mut variables = session.variables.clone();
+    let result = match method {
+        "load" => return load(session, roots, params),
+        "reset" => {
+            let files = json!({ "files": session.files });
+            return load(session, roots, &files)
+        },
+        "add_rules" => {
+            for rule in required_strings(params, "rules")? {
+                parse_line(&mut variables, localize_line(&rule, &session.lang, false), false, true).map_err(Failure::Engine)?;
+            }
+            summary(&variables)
+        },
+        "set_facts" => {
+            set_facts(&mut variables, &required_strings(params, "facts")?, true).map_err(Failure::Engine)?;
+            summary(&variables)
+        },
+        "unset_facts" => {
+            set_facts(&mut variables, &required_strings(params, "facts")?, false).map_err(Failure::Engine)?;
+            summary(&variables)
+        },
+        "query" => {
+            let mut queried = variables.clone();
+            if let Some(queries) = strings(params, "queries")? {
+                queried.values_mut().for_each(|variable| variable.requested = false);
+                add_queries(&mut queried, &queries).map_err(Failure::Engine)?;
+            }
+            Value::Object(query_results(&mut queried, "").into_iter().map(|(name, answer)| (name, Value::String(answer))).collect::<Map<String, Value>>())
+        },
+        "explain" => {
+            let name = params.get("variable").and_then(Value::as_str).map(String::from).ok_or(Failure::Params(Error::NotString(String::from("variable"))))?;
+            let (key, _) = find_named(&variables, &name).ok_or(Failure::Engine(Error::CannotFindVariable(name.clone())))?;
+            let mut explained = variables.clone();
+            match search_query(key, &mut explained, &mut Vec::new(), String::new()) {
+                Ok((value, history)) => json!({
+                    "variable": name,
+                    "answer": match value { true => Answer::True, false => Answer::False }.to_string(),
+                    "trace": history_text(history, &explained, key, &session.lang)
+                }),
+                Err(e) => json!({ "variable": name, "answer": Answer::Undetermined.to_string(), "trace": e.message(&session.lang) })
+            }
+        },
+        "variables" => {
+            let mut keys: Vec<&char> = variables.keys().collect();
+            keys.sort();
+            Value::Array(keys.into_iter().map(|key| variable_json(*key, &variables[key])).collect())
+        },
+        "rules" => Value::Array(unique_rules(&variables).iter().map(|rule| Value::String(rule.to_string().trim().to_string())).collect()),
+        _ => return Err(Failure::Method(method.to_string()))
+    };
+    session.variables = variables;
+    Ok(result)
+}
+
+/* ---------- JSON-RPC ---------- */
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+// The response of one request, none for a notification
+fn answer(session: &mut Session, roots: &[PathBuf], request: &Value) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let method = match (request.get("jsonrpc").and_then(Value::as_str), request.get("method").and_then(Value::as_str)) {
+        (Some("2.0"), Some(method)) => method,
+        _ => return Some(error(id.unwrap_or(Value::Null), INVALID_REQUEST, Error::InvalidRequest.message(&session.lang)))
+    };
+    let params = request.get("params").cloned().unwrap_or(json!({}));
+    let result = call(session, roots, method, &params);
+    Some(match (id?, result) {
+        (id, Ok(result)) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        (id, Err(failure)) => {
+            let (code, message) = failure.code_message(&session.lang);
+            error(id, code, message)
+        }
+    })
+}
+
+fn respond(session: &mut Session, roots: &[PathBuf], line: &str) -> Option<Value> {
+    match serde_json::from_str::<Value>(line) {
+        // an empty batch is itself an invalid request
+        Ok(Value::Array(batch)) if batch.is_empty() => Some(error(Value::Null, INVALID_REQUEST, Error::InvalidRequest.message(&session.lang))),
+        Ok(Value::Array(batch)) => {
+            let responses: Vec<Value> = batch.iter().filter_map(|request| answer(session, roots, request)).collect();
+            match responses.is_empty() {
+                true => None,
+                false => Some(Value::Array(responses))
+            }
+        },
+        Ok(request) => answer(session, roots, &request),
+        Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string()))
+    }
+}
+
+// One request per line and one response per line, until the client closes the connection
+fn handle(reader: impl BufRead, mut writer: impl Write, mut session: Session, roots: &[PathBuf]) {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        if line.trim().is_empty() {
+            continue
+        }
+        if let Some(response) = respond(&mut session, roots, &line) {
+            if writeln!(writer, "{response}").and_then(|_| writer.flush()).is_err() {
+                break
+            }
+        }
+    }
+}
+
+/* ---------- SERVE ---------- */
+// What every connection starts from, the maps of the command line are loaded once
+struct Served {
+    files: Vec<String>,
+    flags: Vec<Flag>,
+    variables: HashMap<char, Variable>,
+    roots: Vec<PathBuf>
+}
+
+impl Served {
+    // A map that cannot be loaded stops the server before it listens
+    fn new(files: &[String], flags: &[Flag]) -> Result<Self, Error> {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        for file in files {
+            fill_maps(&mut variables, file, true).map_err(|e| Error::in_file(file, e))?;
+        }
+        let roots = files.iter().filter_map(|file| Path::new(file).canonicalize().ok()?.parent().map(PathBuf::from)).collect();
+        Ok(Served { files: files.to_vec(), flags: flags.to_vec(), variables, roots })
+    }
+
+    fn session(&self) -> Session {
+        Session::loaded(&self.files, self.variables.clone(), &self.flags)
+    }
+}
+
+// Every connection gets its own session, loaded with the files given on the command line
+pub fn serve(files: &[String], flags: &[Flag], address: &Address) -> Result<(), Error> {
+    // traces are sent as text, without terminal colors
+    set_override(false);
+    let served = Arc::new(Served::new(files, flags)?);
+    match address {
+        Address::Tcp(port) => {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, *port)).map_err(|e| Error::Io(format!("127.0.0.1:{port}"), e.to_string()))?;
+            println!("{}", format!("listening on 127.0.0.1:{port}").blue().bold());
+            for stream in listener.incoming().flatten() {
+                let served = Arc::clone(&served);
+                spawn(move || {
+                    if let Ok(reader) = stream.try_clone() {
+                        handle(BufReader::new(reader), stream, served.session(), &served.roots);
+                    }
+                });
+            }
+        },
+        Address::Unix(path) => serve_unix(served, path)?
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn serve_unix(served: Arc<Served>, path: &str) -> Result<(), Error> {
+    use std::fs::{metadata, remove_file};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+    // a socket left by a previous server is replaced, any other file is kept
+    if metadata(path).map(|m| m.file_type().is_socket()).unwrap_or(false) {
+        remove_file(path).map_err(|e| Error::Io(String::from(path), e.to_string()))?;
+    }
+    let listener = UnixListener::bind(path).map_err(|e| Error::Io(String::from(path), e.to_string()))?;
+    println!("{}", format!("listening on {path}").blue().bold());
+    for stream in listener.incoming().flatten() {
+        let served = Arc::clone(&served);
+        spawn(move || {
+            if let Ok(reader) = stream.try_clone() {
+                handle(BufReader::new(reader), stream, served.session(), &served.roots);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_served: Arc<Served>, path: &str) -> Result<(), Error> {
+    Err(Error::Io(String::from(path), String::from("unix sockets are not supported on this platform")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(session: &mut Session, roots: &[PathBuf], method: &str, params: Value) -> Value {
+        respond(session, roots, &json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string()).unwrap()
+    }
+
+    #[test]
+    fn files_outside_the_served_directories_are_refused() {
+        let served = Served::new(&[String::from("test_files/good/fritz.ru")], &[]).unwrap();
+        let mut session = served.session();
+        let loaded = request(&mut session, &served.roots, "load", json!({ "files": ["test_files/good/loop.ru"] }));
+        assert_eq!(loaded["result"]["rules"], 3);
+        let refused = request(&mut session, &served.roots, "load", json!({ "files": ["Cargo.toml"] }));
+        assert_eq!(refused["error"]["code"], ENGINE_ERROR);
+        assert_eq!(session.variables.len(), 4);
+    }
+
+    #[test]
+    fn errors_of_files_do_not_show_their_lines() {
+        let mut session = Served::new(&[String::from("test_files/good/fritz.ru")], &[]).unwrap().session();
+        let roots = vec![Path::new("test_files").canonicalize().unwrap()];
+        let refused = request(&mut session, &roots, "load", json!({ "files": ["test_files/good/fritz.ru", "test_files/bad/double_then.ru"] }));
+        let message = refused["error"]["message"].as_str().unwrap();
+        assert!(message.starts_with("test_files/bad/double_then.ru: ") && !message.contains("=>"));
+        assert!(Served::new(&[String::from("test_files/bad/double_then.ru")], &[]).is_err());
+    }
+
+    #[test]
+    fn empty_batches_are_invalid_requests() {
+        let served = Served::new(&[], &[]).unwrap();
+        let mut session = served.session();
+        let answered = respond(&mut session, &served.roots, "[]").unwrap();
+        assert_eq!(answered["error"]["code"], INVALID_REQUEST);
+        assert_eq!(answered["id"], Value::Null);
+        let notification = json!([{ "jsonrpc": "2.0", "method": "reset" }]).to_string();
+        assert_eq!(respond(&mut session, &served.roots, &notification), None);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::Path;
+use colored::Colorize;
+use crate::models::{Variable, Answer, ExitStatus};
+use crate::parsing::{fill_maps, fill_source, source_expectations, source_scenarios, scenario_expectations, apply_scenario, Expectation};
+use crate::algo::evaluate;
+use crate::utils::{tick_or_cross, unknown, read_source};
+use crate::translate::{Error, Lang, Translate};
+
+enum Outcome {
+    Pass,
+    Fail(Vec<Error>),
+    Skip
+}
+
+fn resolve(variables: &HashMap<char, Variable>, name: &str, answer: Answer) -> Option<(char, Answer)> {
+    match variables.iter().find(|(k, v)| k.to_string() == name || v.alias_true.as_deref() == Some(name) || v.alias_false.as_deref() == Some(name)) {
+        Some((k, v)) if v.alias_false.as_deref() == Some(name) => Some((*k, answer.negate())),
+        Some((k, _)) => Some((*k, answer)),
+        None => None
+    }
+}
+
+pub fn check_expectations(variables: &HashMap<char, Variable>, answers: &[(char, Answer)], expected: &[(String, Answer)]) -> Vec<Error> {
+    let mut failures = Vec::new();
+    for (name, expected_answer) in expected {
+        let (key, expected_answer) = match resolve(variables, name, *expected_answer) {
+            Some(resolved) => resolved,
+            None => {
+                failures.push(Error::DoesNotExist(name.clone()));
+                continue
+            }
+        };
+        match answers.iter().find(|(k, _)| *k == key) {
+            Some((_, answer)) if *answer == expected_answer => (),
+            Some((_, answer)) => failures.push(Error::ExpectedAnswer(name.clone(), expected_answer.to_string(), answer.to_string())),
+            None => failures.push(Error::NotRequested(name.clone(), expected_answer.to_string()))
+        }
+    }
+    failures
+}
+
+// Errors are written in the active language, an expected error can also be written in english
+fn check_part(loaded: Result<(), Error>, mut variables: HashMap<char, Variable>, expectations: &[Expectation]) -> Vec<Error> {
+    let expected_error = expectations.iter().find_map(|e| match e {Expectation::Error(message) => Some(message), _ => None});
+    let expected: Vec<(String, Answer)> = expectations.iter().filter_map(|e| match e {Expectation::Value(name, answer) => Some((name.clone(), *answer)), _ => None}).collect();
+    match (loaded, expected_error) {
+        (Err(e), Some(message)) if [Lang::current(), Lang::default()].iter().any(|lang| e.message(lang).contains(message.as_str())) => Vec::new(),
+        (Err(e), Some(message)) => vec![Error::ExpectedError(message.clone(), e.to_string())],
+        (Err(e), None) => vec![Error::UnexpectedError(e.to_string())],
+        (Ok(()), Some(message)) => vec![Error::ExpectedErrorValid(message.clone())],
+        (Ok(()), None) => {
+            let queries: Vec<char> = expected.iter().filter_map(|(name, answer)| resolve(&variables, name, *answer)).map(|(k, _)| k).collect();
+            let answers = evaluate(&mut variables, &queries);
+            check_expectations(&variables, &answers, &expected)
+        }
+    }
+}
+
+// The file is read once, a scenario that does not parse fails the file even without expectations
+fn test_file(file: &str) -> Outcome {
+    let contents = match read_source(file) {
+        Ok(contents) => contents,
+        Err(e) => return Outcome::Fail(vec![e])
+    };
+    let expectations = source_expectations(&contents);
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    let (loaded, scenarios) = match fill_source(&mut variables, &contents, true) {
+        Ok(scenarios) => (Ok(()), scenarios),
+        Err(e) => (Err(e), Vec::new())
+    };
+    if expectations.is_empty() && scenarios.iter().all(|scenario| scenario_expectations(scenario).is_empty()) {
+        return match source_scenarios(&contents) {
+            Ok(_) => Outcome::Skip,
+            Err(e) => Outcome::Fail(vec![e])
+        }
+    }
+    let mut failures = check_part(loaded, variables.clone(), &expectations);
+    for scenario in scenarios.iter() {
+        let mut scenario_variables = variables.clone();
+        let applied = apply_scenario(&mut scenario_variables, scenario, true);
+        for failure in check_part(applied, scenario_variables, &scenario_expectations(scenario)) {
+            failures.push(Error::InScenario(scenario.name.clone(), Box::new(failure)));
+        }
+    }
+    match failures.is_empty() {
+        true => Outcome::Pass,
+        false => Outcome::Fail(failures)
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<String>) -> Result<(), Error> {
+    if path.is_dir() {
+        let mut entries: Vec<_> = read_dir(path).map_err(|e| Error::Io(path.display().to_string(), e.to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files)?;
+        }
+    } else if path.extension().map(|ext| ext == "ru").unwrap_or(false) {
+        files.push(path.display().to_string());
+    } else if !path.exists() {
+        Err(Error::DoesNotExist(path.display().to_string()))?
+    }
+    Ok(())
+}
+
+pub fn test_mode(paths: &[String]) -> Result<ExitStatus, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        collect_files(Path::new(path), &mut files)?;
+    }
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for file in files.iter() {
+        match test_file(file) {
+            Outcome::Pass => {
+                println!("{} {file}", tick_or_cross(true));
+                passed += 1;
+            },
+            Outcome::Fail(failures) => {
+                println!("{} {file}", tick_or_cross(false));
+                for failure in failures {
+                    println!("{}", format!("   - {failure}").red());
+                }
+                failed += 1;
+            },
+            Outcome::Skip => {
+                println!("{} {file}", unknown());
+                skipped += 1;
+            }
+        }
+    }
+    println!("{}", Translate::TestSummary.with(&Lang::current(), &[passed.to_string(), failed.to_string(), skipped.to_string()]));
+    match failed {
+        0 => Ok(ExitStatus::AllTrue),
+        _ => Ok(ExitStatus::Unexpected)
+    }
+}
+
+/* ---------- CHECK ---------- */
+fn check_file(file: &str) -> Result<(), Error> {
+    let mut variables: HashMap<char, Variable> = HashMap::new();
+    let scenarios = fill_maps(&mut variables, file, true)?;
+    for scenario in scenarios.iter() {
+        apply_scenario(&mut variables.clone(), scenario, true).map_err(|e| Error::InScenario(scenario.name.clone(), Box::new(e)))?;
+    }
+    Ok(())
+}
+
+// Parses the files without evaluating them, the status is the one of the worst file
+pub fn check_mode(files: &[String]) -> ExitStatus {
+    let mut status = ExitStatus::AllTrue;
+    for file in files {
+        match check_file(file) {
+            Ok(()) => println!("{} {file}", tick_or_cross(true)),
+            Err(e) => {
+                println!("{} {file}", tick_or_cross(false));
+                println!("{}", format!("   - {e}").red());
+                status = status.max(ExitStatus::from_error(&e));
+            }
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expectations_resolve_letters_and_aliases() {
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        fill_source(&mut variables, "def A \"sun\" \"moon\"\n=A\n?A\n", true).unwrap();
+        let answers = [('A', Answer::True)];
+        let expected = [(String::from("A"), Answer::True), (String::from("sun"), Answer::True), (String::from("moon"), Answer::False)];
+        assert!(check_expectations(&variables, &answers, &expected).is_empty());
+        let failures = check_expectations(&variables, &answers, &[(String::from("moon"), Answer::True), (String::from("Z"), Answer::True)]);
+        assert!(matches!(&failures[..], [Error::ExpectedAnswer(..), Error::DoesNotExist(_)]));
+        assert!(matches!(&check_expectations(&variables, &[], &expected[..1])[..], [Error::NotRequested(..)]));
+    }
+
+    #[test]
+    fn expected_errors_match_their_message() {
+        let expected = [Expectation::Error(String::from("loop"))];
+        assert!(check_part(Err(Error::RuleLoop), HashMap::new(), &expected).is_empty());
+        assert!(matches!(&check_part(Err(Error::NoRules), HashMap::new(), &expected)[..], [Error::ExpectedError(..)]));
+        assert!(matches!(&check_part(Ok(()), HashMap::new(), &expected)[..], [Error::ExpectedErrorValid(_)]));
+        assert!(matches!(&check_part(Err(Error::NoRules), HashMap::new(), &[])[..], [Error::UnexpectedError(_)]));
+    }
+
+    #[test]
+    fn files_are_collected_sorted_and_skipped_without_expectations() {
+        let mut files = Vec::new();
+        collect_files(Path::new("test_files/good"), &mut files).unwrap();
+        assert!(!files.is_empty() && files.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(files.iter().all(|file| file.ends_with(".ru")));
+        assert!(matches!(collect_files(Path::new("test_files/none"), &mut files), Err(Error::DoesNotExist(_))));
+        assert!(matches!(test_file("test_files/good/scenarios.ru"), Outcome::Pass));
+        assert!(matches!(test_file("test_files/bad/unknown_keyword.ru"), Outcome::Pass));
+        let untested = std::env::temp_dir().join(format!("expert-system-untested-{}.ru", std::process::id())).display().to_string();
+        std::fs::write(&untested, "=A\nA => B\n?B\n").unwrap();
+        assert!(matches!(test_file(&untested), Outcome::Skip));
+        std::fs::remove_file(&untested).unwrap();
+    }
+
+    #[test]
+    fn broken_scenarios_fail_a_file_without_expectations() {
+        let file = std::env::temp_dir().join(format!("expert-system-tester-{}.ru", std::process::id())).display().to_string();
+        std::fs::write(&file, "A => B\nscenario \"open\" {\n    =A\n").unwrap();
+        assert!(matches!(&test_file(&file), Outcome::Fail(failures) if matches!(failures[..], [Error::UnclosedScenario(_)])));
+        std::fs::remove_file(&file).unwrap();
+    }
+}
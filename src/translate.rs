@@ -1,13 +1,136 @@
+use std::collections::HashMap;
+use std::env::var;
+use std::fs::{read_dir, read_to_string};
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
 use colored::Colorize;
+use crate::utils::{parse_key_values, config_dir};
 
+/* ---------- CATALOGS ---------- */
+const EMBEDDED: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en.lang")),
+    ("fr", include_str!("../locales/fr.lang")),
+    ("it", include_str!("../locales/it.lang"))
+];
+
+static CATALOGS: OnceLock<HashMap<String, HashMap<String, String>>> = OnceLock::new();
+
+// Language of the messages built away from a session, such as parsing errors
+static CURRENT: RwLock<Lang> = RwLock::new(Lang(String::new()));
+
+// Directories holding `<code>.lang` files that override or extend the embedded catalogs
+fn catalog_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = var("EXPERT_SYSTEM_LOCALES") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(dir) = config_dir() {
+        dirs.push(dir.join("locales"));
+    }
+    dirs
+}
+
+fn catalogs() -> &'static HashMap<String, HashMap<String, String>> {
+    CATALOGS.get_or_init(|| {
+        let mut catalogs: HashMap<String, HashMap<String, String>> = EMBEDDED.iter().map(|(code, contents)| (String::from(*code), parse_key_values(contents))).collect();
+        // the first directory wins, so they are read from the last one
+        for dir in catalog_dirs().iter().rev() {
+            for entry in read_dir(dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if let (Some(code), Some("lang")) = (path.file_stem().and_then(|stem| stem.to_str()), path.extension().and_then(|ext| ext.to_str())) {
+                    if let Ok(contents) = read_to_string(&path) {
+                        catalogs.entry(code.to_lowercase()).or_default().extend(parse_key_values(&contents));
+                    }
+                }
+            }
+        }
+        catalogs
+    })
+}
+
+pub(crate) fn available_langs() -> Vec<String> {
+    let mut codes: Vec<String> = catalogs().keys().cloned().collect();
+    codes.sort();
+    codes
+}
+
+/* ---------- LANG ---------- */
 #[derive(PartialEq, Clone)]
-pub enum Lang {
-    En,
-    Fr,
-    It
+pub struct Lang(String);
+
+impl Default for Lang {
+    fn default() -> Self {
+        Lang(String::from("en"))
+    }
+}
+
+impl Lang {
+    pub(crate) fn from_code(code: &str) -> Result<Lang, Error> {
+        let code = code.trim().to_lowercase();
+        match catalogs().contains_key(&code) {
+            true => Ok(Lang(code)),
+            false => Err(Error::InvalidLanguage(code, available_langs().join(", ")))
+        }
+    }
+
+    // Language of LC_ALL, LC_MESSAGES or LANG when a catalog exists for it, as `fr` for `fr_FR.UTF-8`
+    pub(crate) fn from_env() -> Option<Lang> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Lang::from_code(value.split(['_', '.', '@']).next().unwrap_or_default()).ok())
+    }
+
+    pub fn current() -> Lang {
+        CURRENT.read().map(|lang| lang.clone()).unwrap_or_default()
+    }
+
+    pub fn set_current(&self) {
+        if let Ok(mut current) = CURRENT.write() {
+            *current = self.clone();
+        }
+    }
+
+    pub(crate) fn code(&self) -> &str {
+        &self.0
+    }
+
+    fn lookup(&self, key: &str) -> Option<String> {
+        [self.code(), "en"].iter().find_map(|code| catalogs().get(*code).and_then(|catalog| catalog.get(key))).cloned()
+    }
+
+    pub(crate) fn keyword(&self, name: &str) -> Option<String> {
+        self.lookup(&format!("keyword.{name}"))
+    }
+
+    // Message of the key in this language, in english when the catalog misses it
+    pub fn message(&self, key: &str) -> String {
+        self.lookup(key).unwrap_or(String::from(key))
+    }
 }
 
-pub enum Translate {
+// Replaces {1}, {2}... by the arguments in a single pass
+fn fill(template: &str, args: &[String]) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let index = rest[start + 1..].find('}').and_then(|end| rest[start + 1..start + 1 + end].parse::<usize>().ok().map(|i| (i, end)));
+        match index.and_then(|(i, end)| args.get(i.wrapping_sub(1)).map(|arg| (arg, end))) {
+            Some((arg, end)) => {
+                filled.push_str(&rest[..start]);
+                filled.push_str(arg);
+                rest = &rest[start + end + 2..];
+            },
+            None => {
+                filled.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    filled + rest
+}
+
+pub(crate) enum Translate {
     WeKnow,
     WeAlreadyKnow,
     NoRule,
@@ -33,224 +156,334 @@ pub enum Translate {
     HelpRequest,
     HelpDef,
     HelpIf,
+    HelpExportGraph,
+    HelpExportJson,
+    HelpExportDimacs,
+    HelpExportSmt2,
+    HelpExportProlog,
+    HelpImportJson,
+    HelpJournal,
+    HelpReplay,
+    HelpWatch,
     UnknownCommand,
+    Watching,
+    ChangedAtNext,
+    Changed,
+    NoResultChanged,
+    TestSummary,
 }
 
 impl Translate {
+    fn key(&self) -> &str {
+        match self {
+            Translate::WeKnow => "we_know",
+            Translate::WeAlreadyKnow => "we_already_know",
+            Translate::NoRule => "no_rule",
+            Translate::Rule => "rule",
+            Translate::And => "and",
+            Translate::So => "so",
+            Translate::Help => "help",
+            Translate::HelpLanguage => "help_language",
+            Translate::HelpTrace => "help_trace",
+            Translate::HelpReset => "help_reset",
+            Translate::HelpQuit => "help_quit",
+            Translate::HelpVariables => "help_variables",
+            Translate::HelpRules => "help_rules",
+            Translate::HelpClear => "help_clear",
+            Translate::HelpFile => "help_file",
+            Translate::HelpRun => "help_run",
+            Translate::HelpRemoveAll => "help_remove_all",
+            Translate::HelpRemoveVar => "help_remove_var",
+            Translate::HelpRemoveRule => "help_remove_rule",
+            Translate::HelpRemoveRequest => "help_remove_request",
+            Translate::HelpRemoveSet => "help_remove_set",
+            Translate::HelpSet => "help_set",
+            Translate::HelpRequest => "help_request",
+            Translate::HelpDef => "help_def",
+            Translate::HelpIf => "help_if",
+            Translate::HelpExportGraph => "help_export_graph",
+            Translate::HelpExportJson => "help_export_json",
+            Translate::HelpExportDimacs => "help_export_dimacs",
+            Translate::HelpExportSmt2 => "help_export_smt2",
+            Translate::HelpExportProlog => "help_export_prolog",
+            Translate::HelpImportJson => "help_import_json",
+            Translate::HelpJournal => "help_journal",
+            Translate::HelpReplay => "help_replay",
+            Translate::HelpWatch => "help_watch",
+            Translate::UnknownCommand => "unknown_command",
+            Translate::Watching => "watching",
+            Translate::ChangedAtNext => "changed_at_next",
+            Translate::Changed => "changed",
+            Translate::NoResultChanged => "no_result_changed",
+            Translate::TestSummary => "test_summary",
+        }
+    }
+
+    pub(crate) fn text<T: std::fmt::Display>(&self, lang: &Lang, opt_1: T, opt_2: Option<bool>) -> String {
+        let status = match opt_2 {
+            Some(true) => lang.message("true").green(),
+            Some(false) => lang.message("false").red(),
+            None => "".normal()
+        };
+        // a subject given with its value has its own message, as in "A is true"
+        let template = opt_2.and_then(|_| lang.lookup(&format!("{}.status", self.key()))).unwrap_or(lang.message(self.key()));
+        let message = fill(&template, &[opt_1.to_string(), status.to_string()]);
+        match self {
+            Translate::Rule | Translate::And | Translate::So => format!("{message} "),
+            Translate::WeKnow | Translate::WeAlreadyKnow | Translate::NoRule | Translate::UnknownCommand => format!("{message}\n"),
+            _ => format!("{opt_1}\n - {message}\n")
+        }
+    }
+
     pub fn print<T: std::fmt::Display>(&self, lang: &Lang, opt_1: T, opt_2: Option<bool>) {
-        match opt_2 {
-            Some(opt_2) => {
-                match lang {
-                    Lang::En => {
-                        let status = match opt_2 {
-                            true => "true".green(),
-                            false => "false".red()
-                        };
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("We known {} is {} because", opt_1, status),
-                            Translate::WeAlreadyKnow => println!("We already know that {} is {}", opt_1, status),
-                            Translate::NoRule => println!("We know {} is {} because no rule assign it.", opt_1, status),
-                            Translate::Rule => print!("We have{}. ", opt_1),
-                            Translate::And => print!("and {} is {} ", opt_1, status),
-                            Translate::So => print!("so {} is {} ", opt_1, status),
-                            // Help
-                            Translate::Help => println!("{}\n - display all commands or asked one", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - change default language to chosen one", opt_1),
-                            Translate::HelpTrace => println!("{}\n - unable/disable algorithm's trace", opt_1),
-                            Translate::HelpReset => println!("{}\n - clear the map and reload all providen files", opt_1),
-                            Translate::HelpQuit => println!("{}\n - quit the program", opt_1),
-                            Translate::HelpVariables => println!("{}\n - list all variables and their rules", opt_1),
-                            Translate::HelpRules => println!("{}\n - list all rules", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias for \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - read the file in path and enrich variables and rules", opt_1),
-                            Translate::HelpRun => println!("{}\n - run the algorithm with variable if providen", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - clear all variables and rules", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - remove the variable and all rules implicated", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - remove the rule depending the index listed with \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - remove the variable from requested one", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - remove the variable from seted one", opt_1),
-                            Translate::HelpSet => println!("{}\n - set the variable(s) to true", opt_1),
-                            Translate::HelpRequest => println!("{}\n - set the variable(s) to requested", opt_1),
-                            Translate::HelpDef => println!("{}\n - create a new variable", opt_1),
-                            Translate::HelpIf => println!("{}\n - create a new rule", opt_1),
-                            Translate::UnknownCommand => println!("unknown command {}", opt_1)
-                        }
-                    }
-                    Lang::Fr => {
-                        let status = match opt_2 {
-                            true => "vrai".green(),
-                            false => "faux".red()
-                        };
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("Nous savons que {} est {} car", opt_1, status),
-                            Translate::WeAlreadyKnow => println!("Nous savons déjà que {} est {}", opt_1, status),
-                            Translate::NoRule => println!("Nous savons que {} est {} car aucune règle ne l'assigne.", opt_1, status),
-                            Translate::Rule => print!("Nous avons{}. ", opt_1),
-                            Translate::And => print!("et {} est {} ", opt_1, status),
-                            Translate::So => print!("donc {} est {} ", opt_1, status),
-                            // Help
-                            Translate::Help => println!("{}\n - affiche toutes les commandes ou celles demmandées", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - change la langue par celle choisie", opt_1),
-                            Translate::HelpTrace => println!("{}\n - active/désactive la trace de l'alorithme", opt_1),
-                            Translate::HelpReset => println!("{}\n - vide les donnés et ré-importe les fichier", opt_1),
-                            Translate::HelpQuit => println!("{}\n - quitte le programme", opt_1),
-                            Translate::HelpVariables => println!("{}\n - liste toutes les variables et leur règles", opt_1),
-                            Translate::HelpRules => println!("{}\n - liste toutes les règles", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias pour \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - importe le fichier et enrichie les donnés", opt_1),
-                            Translate::HelpRun => println!("{}\n - lance l'algorithme avec les variables si données", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - vide les variables et les règles", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - supprime la variable et toutes les règles l'impliquant", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - supprime la règle selon l'index donné avec \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - supprime la variable des requêtes", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - met la variable a faux", opt_1),
-                            Translate::HelpSet => println!("{}\n - met la variable(s) a vrai", opt_1),
-                            Translate::HelpRequest => println!("{}\n - ajoute la variable(s) aux requêtes", opt_1),
-                            Translate::HelpDef => println!("{}\n - créer une nouvelle variable", opt_1),
-                            Translate::HelpIf => println!("{}\n - créer une nouvelle règle", opt_1),
-                            Translate::UnknownCommand => println!("commande inconnue {}", opt_1)
-                        }
-                    }
-                    Lang::It => {
-                        let status = match opt_2 {
-                            true => "vero".green(),
-                            false => "falso".red()
-                        };
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("Sappiamo che {} è {} perché", opt_1, status),
-                            Translate::WeAlreadyKnow => println!("Sappiamo già che {} è {}", opt_1, status),
-                            Translate::NoRule => println!("Sappiamo che {} è {} perché nessuna regola lo assegna.", opt_1, status),
-                            Translate::Rule => print!("Noi abbiamo{}. ", opt_1),
-                            Translate::And => print!("e {} è {} ", opt_1, status),
-                            Translate::So => print!("così {} è {} ", opt_1, status),
-                            // Help
-                            Translate::Help => println!("{}\n - visualizza tutti i comandi o ne viene chiesto uno", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - cambia lingua in base a quella prescelta", opt_1),
-                            Translate::HelpTrace => println!("{}\n - incapace/disabilita la traccia dell'algoritmo", opt_1),
-                            Translate::HelpReset => println!("{}\n - cancellare i dati e ricaricare tutti i file forniti", opt_1),
-                            Translate::HelpQuit => println!("{}\n - uscire dal programma", opt_1),
-                            Translate::HelpVariables => println!("{}\n - elenca tutte le variabili e le relative regole", opt_1),
-                            Translate::HelpRules => println!("{}\n - elenca tutte le regole", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias per \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - leggi il file nel percorso e arricchisci variabili e regole", opt_1),
-                            Translate::HelpRun => println!("{}\n - eseguire l'algoritmo con la variabile se fornita", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - cancellare tutte le variabili e le regole", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - rimuovere la variabile e tutte le regole implicate", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - rimuovere la regola a seconda dell'indice elencato con \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - rimuovere la variabile da quella richiesta", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - rimuovere la variabile da quella impostata", opt_1),
-                            Translate::HelpSet => println!("{}\n - imposta la variabil.e.i su true", opt_1),
-                            Translate::HelpRequest => println!("{}\n - impostare la variabil.e.i su richiesta", opt_1),
-                            Translate::HelpDef => println!("{}\n - creare una nuova variabile", opt_1),
-                            Translate::HelpIf => println!("{}\n - creare una nuova regola", opt_1),
-                            Translate::UnknownCommand => println!("comando sconosciuto {}", opt_1)
-                        }
-                    }
-                }
-            },
-            None => {
-                match lang {
-                    Lang::En => {
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("We known {} because", opt_1),
-                            Translate::WeAlreadyKnow => println!("We already know that {}", opt_1),
-                            Translate::NoRule => println!("We know {} because no rule assign it.", opt_1),
-                            Translate::Rule => print!("We have{}. ", opt_1),
-                            Translate::And => print!("and {} ", opt_1),
-                            Translate::So => print!("so {} ", opt_1),
-                            // Help
-                            Translate::Help => println!("{}\n - display all commands or asked one", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - change default language to chosen one", opt_1),
-                            Translate::HelpTrace => println!("{}\n - unable/disable algorithm's trace", opt_1),
-                            Translate::HelpReset => println!("{}\n - clear the map and reload all providen files", opt_1),
-                            Translate::HelpQuit => println!("{}\n - quit the program", opt_1),
-                            Translate::HelpVariables => println!("{}\n - list all variables and their rules", opt_1),
-                            Translate::HelpRules => println!("{}\n - list all rules", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias for \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - read the file in path and enrich variables and rules", opt_1),
-                            Translate::HelpRun => println!("{}\n - run the algorithm with variable if providen", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - clear all variables and rules", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - remove the variable and all rules implicated", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - remove the rule depending the index listed with \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - remove the variable from requested one", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - remove the variable from seted one", opt_1),
-                            Translate::HelpSet => println!("{}\n - set the variable(s) to true", opt_1),
-                            Translate::HelpRequest => println!("{}\n - set the variable(s) to requested", opt_1),
-                            Translate::HelpDef => println!("{}\n - create a new variable", opt_1),
-                            Translate::HelpIf => println!("{}\n - create a new rule", opt_1),
-                            Translate::UnknownCommand => println!("unknown command {}", opt_1)
-                        }
-                    }
-                    Lang::Fr => {
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("Nous savons que {} car", opt_1),
-                            Translate::WeAlreadyKnow => println!("Nous savons déjà que {}", opt_1),
-                            Translate::NoRule => println!("Nous savons que {} car aucune règle ne l'assigne.", opt_1),
-                            Translate::Rule => print!("Nous avons{}. ", opt_1),
-                            Translate::And => print!("et {} ", opt_1),
-                            Translate::So => print!("donc {} ", opt_1),
-                            // Help
-                            Translate::Help => println!("{}\n - affiche toutes les commandes ou celles demmandées", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - change la langue par celle choisie", opt_1),
-                            Translate::HelpTrace => println!("{}\n - active/désactive la trace de l'alorithme", opt_1),
-                            Translate::HelpReset => println!("{}\n - vide les donnés et ré-importe les fichier", opt_1),
-                            Translate::HelpQuit => println!("{}\n - quitte le programme", opt_1),
-                            Translate::HelpVariables => println!("{}\n - liste toutes les variables et leur règles", opt_1),
-                            Translate::HelpRules => println!("{}\n - liste toutes les règles", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias pour \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - importe le fichier et enrichie les donnés", opt_1),
-                            Translate::HelpRun => println!("{}\n - lance l'algorithme avec les variables si données", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - vide les variables et les règles", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - supprime la variable et toutes les règles l'impliquant", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - supprime la règle selon l'index donné avec \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - supprime la variable des requêtes", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - met la variable a faux", opt_1),
-                            Translate::HelpSet => println!("{}\n - met la variable(s) a vrai", opt_1),
-                            Translate::HelpRequest => println!("{}\n - ajoute la variable(s) aux requêtes", opt_1),
-                            Translate::HelpDef => println!("{}\n - créer une nouvelle variable", opt_1),
-                            Translate::HelpIf => println!("{}\n - créer une nouvelle règle", opt_1),
-                            Translate::UnknownCommand => println!("commande inconnue {}", opt_1)
-                        }
-                    }
-                    Lang::It => {
-                        match self {
-                            // Trace
-                            Translate::WeKnow => println!("Sappiamo che {} perché", opt_1),
-                            Translate::WeAlreadyKnow => println!("Sappiamo già che {}", opt_1),
-                            Translate::NoRule => println!("Sappiamo che {} perché nessuna regola lo assegna.", opt_1),
-                            Translate::Rule => print!("Noi abbiamo{}. ", opt_1),
-                            Translate::And => print!("e {} ", opt_1),
-                            Translate::So => print!("così {} ", opt_1),
-                            // Help
-                            Translate::Help => println!("{}\n - visualizza tutti i comandi o ne viene chiesto uno", opt_1),
-                            Translate::HelpLanguage => println!("{}\n - cambia lingua in base a quella prescelta", opt_1),
-                            Translate::HelpTrace => println!("{}\n - incapace/disabilita la traccia dell'algoritmo", opt_1),
-                            Translate::HelpReset => println!("{}\n - cancellare i dati e ricaricare tutti i file forniti", opt_1),
-                            Translate::HelpQuit => println!("{}\n - uscire dal programma", opt_1),
-                            Translate::HelpVariables => println!("{}\n - elenca tutte le variabili e le relative regole", opt_1),
-                            Translate::HelpRules => println!("{}\n - elenca tutte le regole", opt_1),
-                            Translate::HelpClear => println!("{}\n - alias per \"remove all\"", opt_1),
-                            Translate::HelpFile => println!("{}\n - leggi il file nel percorso e arricchisci variabili e regole", opt_1),
-                            Translate::HelpRun => println!("{}\n - eseguire l'algoritmo con la variabile se fornita", opt_1),
-                            Translate::HelpRemoveAll => println!("{}\n - cancellare tutte le variabili e le regole", opt_1),
-                            Translate::HelpRemoveVar => println!("{}\n - rimuovere la variabile e tutte le regole implicate", opt_1),
-                            Translate::HelpRemoveRule => println!("{}\n - rimuovere la regola a seconda dell'indice elencato con \"rules\"", opt_1),
-                            Translate::HelpRemoveRequest => println!("{}\n - rimuovere la variabile da quella richiesta", opt_1),
-                            Translate::HelpRemoveSet => println!("{}\n - rimuovere la variabile da quella impostata", opt_1),
-                            Translate::HelpSet => println!("{}\n - imposta la variabil.e.i su true", opt_1),
-                            Translate::HelpRequest => println!("{}\n - impostare la variabil.e.i su richiesta", opt_1),
-                            Translate::HelpDef => println!("{}\n - creare una nuova variabile", opt_1),
-                            Translate::HelpIf => println!("{}\n - creare una nuova regola", opt_1),
-                            Translate::UnknownCommand => println!("comando sconosciuto {}", opt_1)
-                        }
-                    }
-                }
-            }
+        print!("{}", self.text(lang, opt_1, opt_2));
+    }
+
+    // The message alone, for the ones with several subjects or none
+    pub(crate) fn with(&self, lang: &Lang, args: &[String]) -> String {
+        fill(&lang.message(self.key()), args)
+    }
+}
+
+/* ---------- ERRORS ---------- */
+// Errors keep their arguments and are written in a language only when they are shown
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnclosedQuote(String),
+    DefExpectVariable,
+    InvalidVariableName(String),
+    ReservedWord(String),
+    AlreadyAlias(String, String),
+    AliasTooShort(String),
+    AlreadyTrueAlias(String),
+    VariableExists(String),
+    UnexpectedInDef(String),
+    UnexpectedOperator(String),
+    UnexpectedOperatorAfter(String, String),
+    OutputOperators,
+    InputAndOutput(String),
+    UnexpectedInRule(String),
+    UnexpectedClosing,
+    UnclosedDelimiter,
+    Unexpected,
+    ExpectedImplication,
+    SeveralImplications,
+    Contradiction(String),
+    InvalidAnswer(String),
+    ExpectLine,
+    ScenarioStatement(String, String),
+    UnclosedScenarioBefore(String, String),
+    ScenarioName(String),
+    ScenarioBrace(String),
+    UnexpectedAfterScenario(String, String),
+    UnclosedScenario(String),
+    ExpectOnlyInFiles,
+    ExpectedStatement(String),
+    RuleLoop,
+    ParsingFormula,
+    CalculatingFormula,
+    UnclosedQuoteCsv,
+    DoesNotExist(String),
+    UnexpectedCell(String, String),
+    InvalidFact(String, String),
+    EmptyFile(String),
+    NoFileProvided,
+    InvalidLanguage(String, String),
+    NoLanguage(String),
+    NoVariables,
+    NoRules,
+    NoJournal,
+    ExpectedOnOff,
+    ExpectedFormat(String, String),
+    NoFormat(String),
+    ExpectedRemoveKind(String),
+    NoRuleToDelete,
+    ExpectedRuleIndex(String),
+    NotANumber(String),
+    ExpectedNumber,
+    CannotFindVariable(String),
+    ExpectedVariableName,
+    ReplayStopped(String, String, String),
+    ConfigKey(String),
+    ConfigValue(String, String),
+    UnknownEngine(String, String),
+    Io(String, String),
+    InFile(String, Box<Error>),
+    InScenario(String, Box<Error>),
+    InvalidExpectation(String),
+    ExpectedAnswer(String, String, String),
+    NotRequested(String, String),
+    ExpectedError(String, String),
+    UnexpectedError(String),
+    ExpectedErrorValid(String),
+    InvalidJson(String),
+    UnknownRuleVariable(String, String),
+    RuleOutsideOutput(String, String),
+    RuleMissingUnder(String, String),
+    InvalidRequest,
+    MethodNotFound(String),
+    MissingParam(String),
+    NotString(String),
+    NotStrings(String),
+    NotStringOrList(String),
+    UnknownPosition,
+    FileNotServed(String),
+    InvalidLine(String),
+    StdinTwice
+}
+
+impl Error {
+    fn key_args(&self, lang: &Lang) -> (&str, Vec<String>) {
+        match self {
+            Error::UnclosedQuote(a) => ("unclosed_quote", vec![a.clone()]),
+            Error::DefExpectVariable => ("def_expect_variable", Vec::new()),
+            Error::InvalidVariableName(a) => ("invalid_variable_name", vec![a.clone()]),
+            Error::ReservedWord(a) => ("reserved_word", vec![a.clone()]),
+            Error::AlreadyAlias(a, b) => ("already_alias", vec![a.clone(), b.clone()]),
+            Error::AliasTooShort(a) => ("alias_too_short", vec![a.clone()]),
+            Error::AlreadyTrueAlias(a) => ("already_true_alias", vec![a.clone()]),
+            Error::VariableExists(a) => ("variable_exists", vec![a.clone()]),
+            Error::UnexpectedInDef(a) => ("unexpected_in_def", vec![a.clone()]),
+            Error::UnexpectedOperator(a) => ("unexpected_operator", vec![a.clone()]),
+            Error::UnexpectedOperatorAfter(a, b) => ("unexpected_operator_after", vec![a.clone(), b.clone()]),
+            Error::OutputOperators => ("output_operators", Vec::new()),
+            Error::InputAndOutput(a) => ("input_and_output", vec![a.clone()]),
+            Error::UnexpectedInRule(a) => ("unexpected_in_rule", vec![a.clone()]),
+            Error::UnexpectedClosing => ("unexpected_closing", Vec::new()),
+            Error::UnclosedDelimiter => ("unclosed_delimiter", Vec::new()),
+            Error::Unexpected => ("unexpected", Vec::new()),
+            Error::ExpectedImplication => ("expected_implication", Vec::new()),
+            Error::SeveralImplications => ("several_implications", Vec::new()),
+            Error::Contradiction(a) => ("contradiction", vec![a.clone()]),
+            Error::InvalidAnswer(a) => ("invalid_answer", vec![a.clone()]),
+            Error::ExpectLine => ("expect_line", Vec::new()),
+            Error::ScenarioStatement(a, b) => ("scenario_statement", vec![a.clone(), b.clone()]),
+            Error::UnclosedScenarioBefore(a, b) => ("unclosed_scenario_before", vec![a.clone(), b.clone()]),
+            Error::ScenarioName(a) => ("scenario_name", vec![a.clone()]),
+            Error::ScenarioBrace(a) => ("scenario_brace", vec![a.clone()]),
+            Error::UnexpectedAfterScenario(a, b) => ("unexpected_after_scenario", vec![a.clone(), b.clone()]),
+            Error::UnclosedScenario(a) => ("unclosed_scenario", vec![a.clone()]),
+            Error::ExpectOnlyInFiles => ("expect_only_in_files", Vec::new()),
+            Error::ExpectedStatement(a) => ("expected_statement", vec![a.clone()]),
+            Error::RuleLoop => ("rule_loop", Vec::new()),
+            Error::ParsingFormula => ("parsing_formula", Vec::new()),
+            Error::CalculatingFormula => ("calculating_formula", Vec::new()),
+            Error::UnclosedQuoteCsv => ("unclosed_quote_csv", Vec::new()),
+            Error::DoesNotExist(a) => ("does_not_exist", vec![a.clone()]),
+            Error::UnexpectedCell(a, b) => ("unexpected_cell", vec![a.clone(), b.clone()]),
+            Error::InvalidFact(a, b) => ("invalid_fact", vec![a.clone(), b.clone()]),
+            Error::EmptyFile(a) => ("empty_file", vec![a.clone()]),
+            Error::NoFileProvided => ("no_file_provided", Vec::new()),
+            Error::InvalidLanguage(a, b) => ("invalid_language", vec![a.clone(), b.clone()]),
+            Error::NoLanguage(a) => ("no_language", vec![a.clone()]),
+            Error::NoVariables => ("no_variables", Vec::new()),
+            Error::NoRules => ("no_rules", Vec::new()),
+            Error::NoJournal => ("no_journal", Vec::new()),
+            Error::ExpectedOnOff => ("expected_on_off", Vec::new()),
+            Error::ExpectedFormat(a, b) => ("expected_format", vec![a.clone(), b.clone()]),
+            Error::NoFormat(a) => ("no_format", vec![a.clone()]),
+            Error::ExpectedRemoveKind(a) => ("expected_remove_kind", vec![a.clone()]),
+            Error::NoRuleToDelete => ("no_rule_to_delete", Vec::new()),
+            Error::ExpectedRuleIndex(a) => ("expected_rule_index", vec![a.clone()]),
+            Error::NotANumber(a) => ("not_a_number", vec![a.clone()]),
+            Error::ExpectedNumber => ("expected_number", Vec::new()),
+            Error::CannotFindVariable(a) => ("cannot_find_variable", vec![a.clone()]),
+            Error::ExpectedVariableName => ("expected_variable_name", Vec::new()),
+            Error::ReplayStopped(a, b, c) => ("replay_stopped", vec![a.clone(), b.clone(), c.clone()]),
+            Error::ConfigKey(a) => ("config_key", vec![a.clone()]),
+            Error::ConfigValue(a, b) => ("config_value", vec![a.clone(), b.clone()]),
+            Error::UnknownEngine(a, b) => ("unknown_engine", vec![a.clone(), b.clone()]),
+            Error::Io(a, b) => ("io", vec![a.clone(), b.clone()]),
+            Error::InFile(a, e) => ("in_file", vec![a.clone(), e.message(lang)]),
+            Error::InScenario(a, e) => ("in_scenario", vec![a.clone(), e.message(lang)]),
+            Error::InvalidExpectation(a) => ("invalid_expectation", vec![a.clone()]),
+            Error::ExpectedAnswer(a, b, c) => ("expected_answer", vec![a.clone(), b.clone(), c.clone()]),
+            Error::NotRequested(a, b) => ("not_requested", vec![a.clone(), b.clone()]),
+            Error::ExpectedError(a, b) => ("expected_error", vec![a.clone(), b.clone()]),
+            Error::UnexpectedError(a) => ("unexpected_error", vec![a.clone()]),
+            Error::ExpectedErrorValid(a) => ("expected_error_valid", vec![a.clone()]),
+            Error::InvalidJson(a) => ("invalid_json", vec![a.clone()]),
+            Error::UnknownRuleVariable(a, b) => ("unknown_rule_variable", vec![a.clone(), b.clone()]),
+            Error::RuleOutsideOutput(a, b) => ("rule_outside_output", vec![a.clone(), b.clone()]),
+            Error::RuleMissingUnder(a, b) => ("rule_missing_under", vec![a.clone(), b.clone()]),
+            Error::InvalidRequest => ("invalid_request", Vec::new()),
+            Error::MethodNotFound(a) => ("method_not_found", vec![a.clone()]),
+            Error::MissingParam(a) => ("missing_param", vec![a.clone()]),
+            Error::NotString(a) => ("not_string", vec![a.clone()]),
+            Error::NotStrings(a) => ("not_strings", vec![a.clone()]),
+            Error::NotStringOrList(a) => ("not_string_or_list", vec![a.clone()]),
+            Error::UnknownPosition => ("unknown_position", Vec::new()),
+            Error::FileNotServed(a) => ("file_not_served", vec![a.clone()]),
+            Error::InvalidLine(a) => ("invalid_line", vec![a.clone()]),
+            Error::StdinTwice => ("stdin_twice", Vec::new())
+        }
+    }
+
+    pub fn message(&self, lang: &Lang) -> String {
+        let (key, args) = self.key_args(lang);
+        fill(&lang.message(&format!("error.{key}")), &args)
+    }
+
+    // A file is named once, an error reading it already names it
+    pub(crate) fn in_file(file: &str, error: Error) -> Error {
+        match error {
+            Error::Io(..) | Error::InFile(..) => error,
+            error => Error::InFile(String::from(file), Box::new(error))
+        }
+    }
+
+    pub(crate) fn is_contradiction(&self) -> bool {
+        match self {
+            Error::Contradiction(_) => true,
+            Error::InFile(_, e) | Error::InScenario(_, e) => e.is_contradiction(),
+            _ => false
         }
     }
-}
\ No newline at end of file
+}
+
+// Shown in the active language, sessions with their own use `message`
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message(&Lang::current()))
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_written_in_the_language_asked() {
+        let error = Error::DoesNotExist(String::from("Z"));
+        assert_eq!(error.message(&Lang::default()), "Z does not exist");
+        assert_eq!(error.message(&Lang::from_code("fr").unwrap()), "Z n'existe pas");
+        assert_eq!(error.message(&Lang::from_code("it").unwrap()), "Z non esiste");
+    }
+
+    #[test]
+    fn wrapped_errors_keep_their_kind() {
+        let error = Error::in_file("a.ru", Error::InScenario(String::from("s"), Box::new(Error::Contradiction(String::from(" A => B")))));
+        assert!(error.is_contradiction());
+        assert_eq!(error.message(&Lang::default()), "a.ru: [s] contradiction in rule A => B");
+        assert!(!Error::in_file("a.ru", Error::RuleLoop).is_contradiction());
+    }
+
+    #[test]
+    fn a_file_is_named_once() {
+        let error = Error::in_file("a.ru", Error::Io(String::from("a.ru"), String::from("denied")));
+        assert_eq!(error, Error::Io(String::from("a.ru"), String::from("denied")));
+    }
+
+    #[test]
+    fn unknown_languages_are_refused() {
+        assert!(matches!(Lang::from_code("xx"), Err(Error::InvalidLanguage(code, _)) if code == "xx"));
+        assert_eq!(Lang::from_code(" FR ").map(|lang| lang.code().to_string()), Ok(String::from("fr")));
+    }
+
+    #[test]
+    fn missing_arguments_are_left_in_place() {
+        assert_eq!(fill("{1} and {3}", &[String::from("a")]), "a and {3}");
+    }
+}
@@ -1,21 +1,83 @@
 
 use std::collections::HashMap;
+use std::env::var;
+use std::fs::read_to_string;
+use std::io::{stdin, Read};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::models::Variable;
 use colored::{Colorize, ColoredString};
-use crate::translate::{Lang, Translate};
+use crate::translate::{Lang, Translate, Error};
 
-pub fn string_to_char(string: &str) -> char {
+pub(crate) fn parse_key_values(contents: &str) -> HashMap<String, String> {
+    contents.lines().filter_map(|line| {
+        let line = line.trim();
+        match line.starts_with('#') {
+            true => None,
+            false => line.split_once('=').map(|(key, value)| (String::from(key.trim()), String::from(value.trim())))
+        }
+    }).collect()
+}
+
+// Directory of the user settings, `$XDG_CONFIG_HOME/expert-system` or `~/.config/expert-system`
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    match (var("XDG_CONFIG_HOME"), var("HOME")) {
+        (Ok(xdg), _) if !xdg.is_empty() => Some(PathBuf::from(xdg).join("expert-system")),
+        (_, Ok(home)) => Some(PathBuf::from(home).join(".config").join("expert-system")),
+        _ => None
+    }
+}
+
+static STDIN: OnceLock<Result<String, Error>> = OnceLock::new();
+
+// Contents of a rule file, `-` reads stdin once and every later call gets the same contents
+pub(crate) fn read_source(file: &str) -> Result<String, Error> {
+    match file {
+        "-" => STDIN.get_or_init(|| {
+            let mut contents = String::new();
+            stdin().read_to_string(&mut contents).map(|_| contents).map_err(|e| Error::Io(String::from("stdin"), e.to_string()))
+        }).clone(),
+        path => read_to_string(path).map_err(|e| Error::Io(String::from(path), e.to_string()))
+    }
+}
+
+pub(crate) fn string_to_char(string: &str) -> char {
     string.chars().next().unwrap_or('/')
 }
 
-pub fn tick_or_cross(b: bool) -> ColoredString {
-    match b {
-        true =>  "✓".green(),
-        false => "x".red()
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+// Taken by the tests that switch the colors of the process, so that they do not race
+#[cfg(test)]
+pub(crate) static COLORS: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+// Plain mode writes values as words instead of symbols, for screen readers and scripts
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub(crate) fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+pub(crate) fn tick_or_cross(b: bool) -> ColoredString {
+    match (b, is_plain()) {
+        (true, false) =>  "✓".green(),
+        (false, false) => "x".red(),
+        (true, true) => Lang::current().message("true").as_str().green(),
+        (false, true) => Lang::current().message("false").as_str().red()
+    }
+}
+
+pub(crate) fn unknown() -> ColoredString {
+    match is_plain() {
+        false => "ø".yellow(),
+        true => Lang::current().message("unknown").as_str().yellow()
     }
 }
 
-pub fn print_variable(variable: (&char, &Variable)) {
+pub(crate) fn print_variable(variable: (&char, &Variable)) {
     println!("{}: {}", variable.0, variable.1);
     for rule in &variable.1.rules {
         println!(" - {rule}");
@@ -28,37 +90,44 @@ pub fn print_variables(variables: &HashMap<char, Variable>)  {
     }
 }
 
-pub fn print_rules(variables: &HashMap<char, Variable>)  {
-    let mut i = 0;
-    for (_, v) in variables.iter() {
-        for rule in &v.rules {
-            i += 1;
-            println!("[{i}] {rule}");
-        }
+// The variable and position of each rule, by letter then position so that a number names the same
+// rule in every run, as `remove rule` in a journal or a script needs
+pub(crate) fn numbered_rules(variables: &HashMap<char, Variable>) -> Vec<(char, usize)> {
+    let mut keys: Vec<&char> = variables.keys().collect();
+    keys.sort();
+    keys.into_iter().flat_map(|key| (0..variables[key].rules.len()).map(|i| (*key, i))).collect()
+}
+
+pub(crate) fn print_rules(variables: &HashMap<char, Variable>)  {
+    for (i, (key, position)) in numbered_rules(variables).into_iter().enumerate() {
+        println!("[{}] {}", i + 1, variables[&key].rules[position]);
     }
 }
 
-fn print_false_no_rule(mut s: String, variables: &HashMap<char, Variable>, lang: &Lang) {
+fn false_no_rule_text(mut s: String, variables: &HashMap<char, Variable>, lang: &Lang) -> String {
+    let mut text = String::new();
     let value: char = s.pop().unwrap();
     if let Some(var) = variables.get(&value) {
         match var.value {
             true => {
                 match &var.alias_true {
-                    Some(alias) => Translate::NoRule.print(lang, alias.green(), None),
-                    _ => Translate::NoRule.print(lang, value.to_string().purple().bold(), Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::NoRule.text(lang, alias.green(), None)),
+                    _ => text.push_str(&Translate::NoRule.text(lang, value.to_string().purple().bold(), Some(var.value))),
                 }
             },
             false => {
                 match &var.alias_false {
-                    Some(alias) => Translate::NoRule.print(lang, alias.red(), None),
-                    _ => Translate::NoRule.print(lang, value.to_string().purple().bold(), Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::NoRule.text(lang, alias.red(), None)),
+                    _ => text.push_str(&Translate::NoRule.text(lang, value.to_string().purple().bold(), Some(var.value))),
                 }
             },
         }
     }
+    text
 }
 
-fn print_rules_path(s: String, variables: &HashMap<char, Variable>, query: char, lang: &Lang) {
+fn rules_path_text(s: String, variables: &HashMap<char, Variable>, query: char, lang: &Lang) -> String {
+    let mut text = String::new();
     let formula = s.trim_start_matches('r').chars().fold((String::new(), false), |(mut acc, is_neg), c| {
         match c {
             '!' => {
@@ -79,7 +148,7 @@ fn print_rules_path(s: String, variables: &HashMap<char, Variable>, query: char,
             }
         }
     }).0;
-    Translate::Rule.print(lang, formula.blue().bold(), None);
+    text.push_str(&Translate::Rule.text(lang, formula.blue().bold(), None));
     let mut implies_bool = false;
     let mut conjuction_word = Translate::And;
     for c in s.chars() {
@@ -101,57 +170,62 @@ fn print_rules_path(s: String, variables: &HashMap<char, Variable>, query: char,
                 match var.value {
                     true => {
                         match &var.alias_true {
-                            Some(alias) => conjuction_word.print(lang, alias.green(), None),
-                            _ => conjuction_word.print(lang, name, Some(var.value)),
+                            Some(alias) => text.push_str(&conjuction_word.text(lang, alias.green(), None)),
+                            _ => text.push_str(&conjuction_word.text(lang, name, Some(var.value))),
                         }
                     },
                     false => {
                         match &var.alias_false {
-                            Some(alias) => conjuction_word.print(lang, alias.red(), None),
-                            _ => conjuction_word.print(lang, name, Some(var.value)),
+                            Some(alias) => text.push_str(&conjuction_word.text(lang, alias.red(), None)),
+                            _ => text.push_str(&conjuction_word.text(lang, name, Some(var.value))),
                         }
                     },
                 };
             }
         }
     }
-    print!("\n");
+    text.push('\n');
+    text
 }
 
-fn print_already_know(mut s: String, variables: &HashMap<char, Variable>, lang: &Lang) {
+fn already_know_text(mut s: String, variables: &HashMap<char, Variable>, lang: &Lang) -> String {
+    let mut text = String::new();
     let value: char = s.pop().unwrap();
     if let Some(var) = variables.get(&value) {
         match var.value {
             true => {
                 match &var.alias_true {
-                    Some(alias) => Translate::WeAlreadyKnow.print(lang, alias.green(), None),
-                    _ => Translate::WeAlreadyKnow.print(lang, value.to_string().purple().bold(),Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::WeAlreadyKnow.text(lang, alias.green(), None)),
+                    _ => text.push_str(&Translate::WeAlreadyKnow.text(lang, value.to_string().purple().bold(),Some(var.value))),
                 }
             },
             false => {
                 match &var.alias_false {
-                    Some(alias) => Translate::WeAlreadyKnow.print(lang, alias.red(), None),
-                    _ => Translate::WeAlreadyKnow.print(lang, value.to_string().purple().bold(), Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::WeAlreadyKnow.text(lang, alias.red(), None)),
+                    _ => text.push_str(&Translate::WeAlreadyKnow.text(lang, value.to_string().purple().bold(), Some(var.value))),
                 }
             },
         }
     }
+    text
 }
 
-pub fn print_history(history: String, variables: &HashMap<char, Variable>, query: char, lang: &Lang) {
+// Explanation of how a query was answered, from the history built by `search_query`
+pub(crate) fn history_text(history: String, variables: &HashMap<char, Variable>, query: char, lang: &Lang) -> String {
+    let mut text = String::new();
     let paths: Vec<&str> = history.split('%').collect();
     if let Some(var) = variables.get(&query) {
         match var.value {
             true => {
                 match &var.alias_true {
-                    Some(alias) => Translate::WeKnow.print(&lang, alias.green(), None),
-                    _ => Translate::WeKnow.print(&lang, query.to_string().purple().bold(), Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::WeKnow.text(&lang, alias.green(), None)),
+                    _ => text.push_str(&Translate::WeKnow.text(&lang, query.to_string().purple().bold(), Some(var.value))),
                 }
             },
             false => {
                 match &var.alias_false {
-                    Some(alias) => Translate::WeKnow.print(&lang, alias.red(), None),
-                    _ => Translate::WeKnow.print(&lang, query.to_string().purple().bold(), Some(var.value)),
+                    Some(alias) => text.push_str(&Translate::WeKnow.text(&lang, alias.red(), None)),
+                    _ => text.push_str(&Translate::WeKnow.text(&lang, query.to_string().purple().bold(), Some(var.value))),
                 }
             },
         }
@@ -159,11 +233,16 @@ pub fn print_history(history: String, variables: &HashMap<char, Variable>, query
     for path in paths.iter() {
         if path.len() > 0 {
             match path.chars().next().unwrap() {
-                'r' => print_rules_path(path.to_string(), variables, query, lang),
-                'n' => print_false_no_rule(path.to_string(), variables, lang),
-                'i' => print_already_know(path.to_string(), variables, lang),
+                'r' => text.push_str(&rules_path_text(path.to_string(), variables, query, lang)),
+                'n' => text.push_str(&false_no_rule_text(path.to_string(), variables, lang)),
+                'i' => text.push_str(&already_know_text(path.to_string(), variables, lang)),
                 _ => unreachable!()
             }
         }
     }
-}
\ No newline at end of file
+    text
+}
+
+pub(crate) fn print_history(history: String, variables: &HashMap<char, Variable>, query: char, lang: &Lang) {
+    print!("{}", history_text(history, variables, query, lang));
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fs::metadata;
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, SystemTime};
+use colored::{Colorize, ColoredString};
+use crate::models::{Variable, Answer};
+use crate::parsing::{fill_maps, apply_scenario};
+use crate::algo::evaluate;
+use crate::translate::{Error, Lang, Translate};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/* ---------- WATCHER ---------- */
+pub(crate) struct Watcher {
+    mtimes: HashMap<String, Option<SystemTime>>
+}
+
+fn mtime(file: &str) -> Option<SystemTime> {
+    metadata(file).and_then(|m| m.modified()).ok()
+}
+
+impl Watcher {
+    pub fn new(files: &[String]) -> Self {
+        Watcher { mtimes: files.iter().map(|file| (file.clone(), mtime(file))).collect() }
+    }
+
+    // Files modified since the last call, a file seen for the first time is not a change
+    pub(crate) fn changed(&mut self, files: &[String]) -> Vec<String> {
+        let mut changed = Vec::new();
+        for file in files {
+            let current = mtime(file);
+            if let Some(previous) = self.mtimes.insert(file.clone(), current) {
+                if previous != current {
+                    changed.push(file.clone());
+                }
+            }
+        }
+        changed
+    }
+}
+
+// Files changed since their owner last took them
+pub(crate) type Changes = Arc<Mutex<Vec<String>>>;
+
+// Polls the files on its own thread, which stops once the returned list is dropped. A change is
+// announced as soon as it is seen and kept in the list until its owner takes it at its next command
+pub(crate) fn poll_changes(files: &[String], lang: &Lang) -> Changes {
+    let changed = Arc::new(Mutex::new(Vec::new()));
+    let (files, lang, shared) = (files.to_vec(), lang.clone(), Arc::clone(&changed));
+    // the files are seen before the thread starts, a change made right after is not missed
+    let mut watcher = Watcher::new(&files);
+    spawn(move || {
+        while Arc::strong_count(&shared) > 1 {
+            sleep(POLL_INTERVAL);
+            let new = watcher.changed(&files);
+            if new.is_empty() {
+                continue
+            }
+            println!("\n{}", Translate::ChangedAtNext.with(&lang, &[new.join(", ")]).blue().bold());
+            if let Ok(mut changed) = shared.lock() {
+                changed.extend(new);
+            }
+        }
+    });
+    changed
+}
+
+/* ---------- RESULTS ---------- */
+pub(crate) fn query_results(variables: &mut HashMap<char, Variable>, prefix: &str) -> Vec<(String, String)> {
+    let mut queries: Vec<char> = variables.iter().filter(|(_, v)| v.requested).map(|(k, _)| *k).collect();
+    queries.sort();
+    evaluate(variables, &queries).into_iter().map(|(key, answer)| {
+        let name = variables.get(&key).and_then(|v| v.alias_true.clone()).unwrap_or(key.to_string());
+        (format!("{prefix}{name}"), answer.to_string())
+    }).collect()
+}
+
+// Results of every file as a normal run would print them, scenarios and command line facts included
+pub fn file_results(files: &[String], overrides: impl Fn(&mut HashMap<char, Variable>) -> Result<(), Error>) -> Result<Vec<(String, String)>, Error> {
+    let mut results = Vec::new();
+    for file in files {
+        let prefix = match files.len() {
+            1 => String::new(),
+            _ => format!("{file} ")
+        };
+        let mut variables: HashMap<char, Variable> = HashMap::new();
+        let scenarios = fill_maps(&mut variables, file, true).map_err(|e| Error::in_file(file, e))?;
+        overrides(&mut variables).map_err(|e| Error::in_file(file, e))?;
+        if scenarios.is_empty() || variables.values().any(|v| v.requested) {
+            results.extend(query_results(&mut variables.clone(), &prefix));
+        }
+        for scenario in scenarios.iter() {
+            let mut scenario_variables = variables.clone();
+            apply_scenario(&mut scenario_variables, scenario, true).map_err(|e| Error::in_file(file, e))?;
+            overrides(&mut scenario_variables).map_err(|e| Error::in_file(file, e))?;
+            results.extend(query_results(&mut scenario_variables, &format!("{prefix}[{}] ", scenario.name)));
+        }
+    }
+    Ok(results)
+}
+
+fn color_answer(answer: &str) -> ColoredString {
+    match Answer::from_str(answer) {
+        Some(Answer::True) => answer.green(),
+        Some(Answer::False) => answer.red(),
+        _ => answer.yellow()
+    }
+}
+
+pub(crate) fn print_diff(previous: &[(String, String)], current: &[(String, String)]) {
+    let mut unchanged = true;
+    for (name, answer) in current.iter() {
+        match previous.iter().find(|(n, _)| n == name) {
+            Some((_, old)) if old == answer => (),
+            Some((_, old)) => println!("{} {name}: {} -> {}", "~".yellow(), color_answer(old), color_answer(answer)),
+            None => println!("{} {name}: {}", "+".green(), color_answer(answer))
+        }
+        unchanged &= previous.iter().any(|(n, old)| n == name && old == answer);
+    }
+    for (name, _) in previous.iter().filter(|(name, _)| !current.iter().any(|(n, _)| n == name)) {
+        println!("{} {name}", "-".red());
+        unchanged = false;
+    }
+    if unchanged {
+        println!("{}", Translate::NoResultChanged.with(&Lang::current(), &[]).dimmed());
+    }
+}
+
+/* ---------- WATCH ---------- */
+// Polls the files and prints how the results moved each time one of them changes
+pub fn watch(files: &[String], results: impl Fn() -> Result<Vec<(String, String)>, Error>) -> ! {
+    let mut watcher = Watcher::new(files);
+    let mut previous = match results() {
+        Ok(current) => {
+            print_diff(&[], &current);
+            current
+        },
+        Err(e) => {
+            println!("{}", e.to_string().red());
+            Vec::new()
+        }
+    };
+    println!("{}", Translate::Watching.with(&Lang::current(), &[files.join(", ")]).blue().bold());
+    loop {
+        sleep(POLL_INTERVAL);
+        let changed = watcher.changed(files);
+        if changed.is_empty() {
+            continue
+        }
+        println!("{}", Translate::Changed.with(&Lang::current(), &[changed.join(", ")]).blue().bold());
+        match results() {
+            Ok(current) => {
+                print_diff(&previous, &current);
+                previous = current;
+            },
+            Err(e) => println!("{}", e.to_string().red())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{write, remove_file, File};
+
+    #[test]
+    fn only_modified_files_are_changes() {
+        let file = std::env::temp_dir().join(format!("expert-system-watch-{}.ru", std::process::id())).display().to_string();
+        write(&file, "=A\n?A\n").unwrap();
+        let files = vec![file.clone()];
+        let mut watcher = Watcher::new(&files);
+        assert!(watcher.changed(&files).is_empty());
+        File::options().write(true).open(&file).unwrap().set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(watcher.changed(&files), files);
+        assert!(watcher.changed(&files).is_empty());
+        let other = vec![String::from("test_files/good/scenarios.ru")];
+        assert!(watcher.changed(&other).is_empty());
+        remove_file(&file).unwrap();
+        assert_eq!(watcher.changed(&files), files);
+    }
+
+    #[test]
+    fn results_name_scenarios_and_take_overrides() {
+        let files = vec![String::from("test_files/good/scenarios.ru")];
+        let results = file_results(&files, |_| Ok(())).unwrap();
+        assert!(results.contains(&(String::from("[frog] It's 🟢"), String::from("true"))));
+        assert!(results.contains(&(String::from("[duck] It's a 🦆"), String::from("true"))));
+        let results = file_results(&files, |variables| crate::parsing::set_facts(variables, &[String::from("B")], false)).unwrap();
+        assert!(results.contains(&(String::from("[duck] It's a 🦆"), String::from("false"))));
+        let files = vec![files[0].clone(); 2];
+        assert!(file_results(&files, |_| Ok(())).unwrap().iter().all(|(name, _)| name.starts_with("test_files/good/scenarios.ru [")));
+    }
+}